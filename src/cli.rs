@@ -1,17 +1,56 @@
-use std::path::PathBuf;
+use std::{net::IpAddr, path::PathBuf};
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::logging::{LogFormat, LogRotation};
 
 /// Server used for match making mancala games
 #[derive(Parser)]
+#[command(subcommand_negates_reqs = true)]
 pub struct Args {
-    /// The port the server should bind to.
+    /// Path to the TOML configuration file. Its values can be overridden by environment
+    /// variables (e.g. MANCALA_MATCHES_MOVE_TIMEOUT=5s for move_timeout in the [matches]
+    /// section), which can themselves be overridden by the command line. Sending SIGHUP to the
+    /// server reloads it.
+    #[arg(short, long, env = "MANCALA_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// The port the server should bind to, overriding server.port.
     #[arg(short, long)]
-    pub port: u16,
+    pub port: Option<u16>,
+
+    /// An address the server should listen on, overriding server.addresses. Can be given
+    /// multiple times to listen on multiple addresses (IPv4 or IPv6).
+    #[arg(short, long = "address")]
+    pub addresses: Vec<IpAddr>,
+
+    /// Path to the PEM encoded TLS certificate chain, overriding server.tls. The server is
+    /// then served over HTTPS and bots connect over wss://.
+    #[arg(long, requires = "tls_key")]
+    pub tls_certificate: Option<PathBuf>,
+
+    /// Path to the PEM encoded private key of the TLS certificate.
+    #[arg(long, requires = "tls_certificate")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Overrides any configuration value, e.g. --set matches.move_timeout=5s. Can be given
+    /// multiple times.
+    #[arg(long = "set", value_name = "SECTION.KEY=VALUE", value_parser = parse_override)]
+    pub overrides: Vec<(String, String)>,
 
     /// Path to the database used to store bot data and information.
-    #[arg(short, long)]
-    pub database: PathBuf,
+    #[arg(
+        short,
+        long,
+        env = "MANCALA_DATABASE",
+        required_unless_present = "in_memory"
+    )]
+    pub database: Option<PathBuf>,
+
+    /// Store everything in memory instead of in a database. Nothing will be kept once the
+    /// server shuts down, which is mostly useful for tests and development.
+    #[arg(long, conflicts_with_all = ["database", "migrate_only"])]
+    pub in_memory: bool,
 
     /// Specifies the level of tracing for the server. Possible values are: TRACE,
     /// DEBUG, INFO, WARN and ERROR; with TRACE implying DEBUG and so on and so forth.
@@ -19,6 +58,119 @@ pub struct Args {
     #[arg(short, long, default_value_t = tracing::Level::WARN)]
     pub log: tracing::Level,
 
-    #[arg(short, long)]
+    /// Format of the logs, both on the standard output and in the log files.
+    #[arg(long, env = "MANCALA_LOG_FORMAT", default_value_t = LogFormat::Text, value_enum)]
+    pub log_format: LogFormat,
+
+    /// Also write logs to files in the given directory, rotated as per --log-rotation.
+    #[arg(long, env = "MANCALA_LOG_DIRECTORY")]
+    pub log_directory: Option<PathBuf>,
+
+    /// How often a new log file is started in --log-directory. Ignored without one.
+    #[arg(
+        long,
+        env = "MANCALA_LOG_ROTATION",
+        default_value_t = LogRotation::Daily,
+        value_enum
+    )]
+    pub log_rotation: LogRotation,
+
+    /// Directory of the static files (such as the frontend generated by trunk) served for every
+    /// path that is not part of the API. Nothing is served if it does not exist.
+    #[arg(
+        short,
+        long,
+        env = "MANCALA_STATIC_ROUTES",
+        default_value = "frontend/dist"
+    )]
     pub static_routes: PathBuf,
+
+    /// Only bring the database's schema up to date by applying pending migrations, then exit
+    /// without starting the server.
+    #[arg(long)]
+    pub migrate_only: bool,
+
+    /// Run a maintenance command on the database instead of starting the server.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Args {
+    /// Returns every configuration value given on the command line, as (`section.key`, value)
+    /// pairs.
+    pub fn config_overrides(&self) -> Vec<(String, String)> {
+        let mut overrides = Vec::new();
+        if !self.addresses.is_empty() {
+            let addresses: Vec<_> = self
+                .addresses
+                .iter()
+                .map(|address| format!("\"{address}\""))
+                .collect();
+            overrides.push((
+                "server.addresses".to_owned(),
+                format!("[{}]", addresses.join(", ")),
+            ));
+        }
+        if let (Some(certificate), Some(key)) = (&self.tls_certificate, &self.tls_key) {
+            let tls = toml::Value::Table(toml::Table::from_iter([
+                (
+                    "certificate".to_owned(),
+                    certificate.display().to_string().into(),
+                ),
+                ("key".to_owned(), key.display().to_string().into()),
+            ]));
+            overrides.push(("server.tls".to_owned(), format!("{tls}")));
+        }
+        if let Some(port) = self.port {
+            overrides.push(("server.port".to_owned(), port.to_string()));
+        }
+        // Explicit overrides win over the shorthands above.
+        overrides.extend(self.overrides.iter().cloned());
+        overrides
+    }
+}
+
+fn parse_override(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.trim().to_owned(), value.trim().to_owned()))
+        .ok_or_else(|| format!("expected SECTION.KEY=VALUE, got {value}"))
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Snapshot the database to a new file. This is safe to run whilst the server is up.
+    Backup {
+        /// Path of the snapshot, which must not already exist.
+        destination: PathBuf,
+    },
+
+    /// Export every bot, its rating, the match history and the practice matches.
+    Export {
+        /// Where to export to: a file for JSON, a directory for CSV.
+        path: PathBuf,
+
+        #[arg(short, long, default_value_t = Format::Json, value_enum)]
+        format: Format,
+    },
+
+    /// Import bots, their ratings, match history and practice matches that were previously
+    /// exported. The names of the imported bots must not already be taken.
+    Import {
+        /// What to import from: a file for JSON, a directory for CSV.
+        path: PathBuf,
+
+        #[arg(short, long, default_value_t = Format::Json, value_enum)]
+        format: Format,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum Format {
+    /// A single JSON file.
+    Json,
+
+    /// A directory containing a bots.csv, a matches.csv and a practice_matches.csv file (which
+    /// snapshots exported before practice matches were may lack).
+    Csv,
 }
@@ -0,0 +1,87 @@
+use std::path::Path;
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result as EyreResult,
+};
+use match_server::{
+    config::ConfigSources,
+    server::{
+        database::{self, Database},
+        storage::transfer,
+    },
+};
+
+use crate::cli::{Command, Format};
+
+/// Runs a maintenance command on the database found at the given path, reading the
+/// configuration from the given sources if the command needs it.
+pub async fn run(command: Command, database: &Path, sources: &ConfigSources) -> EyreResult<()> {
+    match command {
+        Command::Backup { destination } => {
+            // The backup API overwrites the destination, which could be a previous backup.
+            if destination.exists() {
+                bail!("{} already exists", destination.display());
+            }
+            if !database.exists() {
+                bail!("{} does not exist", database.display());
+            }
+
+            database::backup(database, &destination).wrap_err("Could not backup the database")?;
+            println!(
+                "Backed up {} to {}",
+                database.display(),
+                destination.display()
+            );
+        }
+
+        Command::Export { path, format } => {
+            // Bots of legacy databases may have no elo, in which case they are given the one
+            // new bots start with.
+            let starting_elo = sources
+                .load()
+                .wrap_err("Could not load the configuration")?
+                .matchmaking
+                .starting_elo;
+
+            let storage = Database::open(database).wrap_err("Could not open the database")?;
+            let snapshot = transfer::export(&storage, starting_elo)
+                .await
+                .wrap_err("Could not read the database")?;
+
+            match format {
+                Format::Json => transfer::write_json(&snapshot, &path),
+                Format::Csv => transfer::write_csv(&snapshot, &path),
+            }
+            .wrap_err_with(|| format!("Could not export to {}", path.display()))?;
+
+            println!(
+                "Exported {} bots, {} matches and {} practice matches to {}",
+                snapshot.bots.len(),
+                snapshot.matches.len(),
+                snapshot.practice_matches.len(),
+                path.display()
+            );
+        }
+
+        Command::Import { path, format } => {
+            let snapshot = match format {
+                Format::Json => transfer::read_json(&path),
+                Format::Csv => transfer::read_csv(&path),
+            }
+            .wrap_err_with(|| format!("Could not read {}", path.display()))?;
+
+            let storage = Database::open(database).wrap_err("Could not open the database")?;
+            let counts = transfer::import(&storage, snapshot)
+                .await
+                .wrap_err("Could not import into the database")?;
+
+            println!(
+                "Imported {} bots, {} matches and {} practice matches",
+                counts.bots, counts.matches, counts.practice_matches
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,380 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::sync::watch;
+use toml::{Table, Value};
+use tracing::{error, info, warn};
+
+use crate::mancala::opening::{Openings, MAX_RANDOM_MOVES};
+
+mod tests;
+
+/// Prefix of the environment variables overriding the configuration file. The rest of the name
+/// is the section followed by the key, so `MANCALA_MATCHES_MOVE_TIMEOUT=5s` sets `move_timeout`
+/// in the `[matches]` section.
+pub const ENV_PREFIX: &str = "MANCALA_";
+
+/// Sections of the configuration, which can be overridden through environment variables.
+const SECTIONS: [&str; 5] = ["server", "matchmaking", "matches", "presence", "shutdown"];
+
+/// The server's whole configuration. Every value has a default, so an empty file (or no file
+/// at all) is a valid configuration.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub matchmaking: MatchmakingSettings,
+    pub matches: MatchSettings,
+    pub presence: PresenceSettings,
+    pub shutdown: ShutdownSettings,
+}
+
+/// Values that are only read once when the server starts, and thus can't be reloaded.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Addresses the server listens on, each of them on the same port. Note that on most
+    /// systems, listening on `::` also accepts IPv4 connections.
+    pub addresses: Vec<IpAddr>,
+    /// Port the server binds to, which must be given one way or another.
+    pub port: Option<u16>,
+    /// Maximum amount of connections opened to the database at once.
+    pub max_connections: usize,
+    /// Serves every listener over TLS (and thus bots over `wss://`) if given.
+    pub tls: Option<TlsConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            addresses: vec![Ipv4Addr::LOCALHOST.into()],
+            port: None,
+            max_connections: 8,
+            tls: None,
+        }
+    }
+}
+
+/// Paths of the PEM encoded files used for TLS.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// The certificate chain, starting with the server's certificate.
+    pub certificate: PathBuf,
+    /// The certificate's private key.
+    pub key: PathBuf,
+}
+
+/// Every value that can be changed whilst the server is running, see [`reload_on_hangup`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Settings {
+    pub matchmaking: MatchmakingSettings,
+    pub matches: MatchSettings,
+    pub presence: PresenceSettings,
+    pub shutdown: ShutdownSettings,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchmakingSettings {
+    /// Time between two rounds of matchmaking.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    /// Elo newly registered bots start with.
+    pub starting_elo: u16,
+    /// Maximum amount of matches a bot plays at once.
+    pub max_concurrent_games: u8,
+    /// How long two bots that played each other are kept apart, unless there is nobody else left
+    /// for them to play.
+    #[serde(with = "humantime_serde")]
+    pub rematch_cooldown: Duration,
+}
+
+impl Default for MatchmakingSettings {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            starting_elo: 1000,
+            max_concurrent_games: 2,
+            rematch_cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchSettings {
+    /// How many times a bot can fail to be reached before being disqualified.
+    pub connection_retries: u8,
+    /// How many invalid answers a bot can give before being disqualified.
+    pub query_retries: u8,
+    /// How long a bot has to answer a query before being disqualified.
+    #[serde(with = "humantime_serde")]
+    pub move_timeout: Duration,
+    /// How long a bot whose connection dropped mid-match is waited for before being
+    /// disqualified.
+    #[serde(with = "humantime_serde")]
+    pub reconnection_grace: Duration,
+    /// How the starting position of each match is picked.
+    pub openings: Openings,
+    /// Amount of random moves played from the standard position when openings are random moves.
+    pub opening_moves: u8,
+    /// Lets the player moving second trade sides with its opponent after the first move, to
+    /// make up for the advantage of moving first.
+    pub pie_rule: bool,
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        Self {
+            connection_retries: 8,
+            query_retries: 2,
+            move_timeout: Duration::from_secs(10),
+            reconnection_grace: Duration::from_secs(30),
+            openings: Openings::Standard,
+            opening_moves: 4,
+            pie_rule: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PresenceSettings {
+    /// Time between two rounds of pings.
+    #[serde(with = "humantime_serde")]
+    pub heartbeat_interval: Duration,
+    /// How long a bot has to answer a ping before being considered gone.
+    #[serde(with = "humantime_serde")]
+    pub pong_timeout: Duration,
+}
+
+impl Default for PresenceSettings {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval: Duration::from_secs(10),
+            pong_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownSettings {
+    /// How long running matches are given to finish once the server is asked to shut down,
+    /// after which they are aborted.
+    #[serde(with = "humantime_serde")]
+    pub drain_deadline: Duration,
+}
+
+impl Default for ShutdownSettings {
+    fn default() -> Self {
+        Self {
+            drain_deadline: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Config {
+    /// Returns the values that can be changed whilst the server is running.
+    pub fn settings(&self) -> Settings {
+        Settings {
+            matchmaking: self.matchmaking,
+            matches: self.matches,
+            presence: self.presence,
+            shutdown: self.shutdown,
+        }
+    }
+
+    /// Checks that the values make sense together.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |message: &str| Err(ConfigError::Invalid(message.to_owned()));
+
+        if self.server.addresses.is_empty() {
+            return invalid("server.addresses must contain at least one address");
+        }
+        if self.server.max_connections == 0 {
+            return invalid("server.max_connections must be at least 1");
+        }
+        if self.matchmaking.interval.is_zero() {
+            return invalid("matchmaking.interval must not be zero");
+        }
+        if self.matchmaking.max_concurrent_games == 0 {
+            return invalid("matchmaking.max_concurrent_games must be at least 1");
+        }
+        if self.matches.connection_retries == 0 || self.matches.query_retries == 0 {
+            return invalid(
+                "matches.connection_retries and matches.query_retries must be at least 1",
+            );
+        }
+        if self.matches.move_timeout.is_zero() {
+            return invalid("matches.move_timeout must not be zero");
+        }
+        if self.matches.opening_moves > MAX_RANDOM_MOVES {
+            return invalid(&format!(
+                "matches.opening_moves must be at most {MAX_RANDOM_MOVES}"
+            ));
+        }
+        if self.presence.pong_timeout.is_zero()
+            || self.presence.pong_timeout >= self.presence.heartbeat_interval
+        {
+            return invalid(
+                "presence.pong_timeout must be shorter than presence.heartbeat_interval",
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Where the configuration comes from, from lowest to highest priority: the defaults, the
+/// configuration file, the environment variables and the command line overrides.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigSources {
+    pub file: Option<PathBuf>,
+    /// Values given on the command line, as (`section.key`, value) pairs.
+    pub overrides: Vec<(String, String)>,
+}
+
+impl ConfigSources {
+    /// Loads and validates the configuration, reading the process' environment.
+    pub fn load(&self) -> Result<Config, ConfigError> {
+        self.load_with_env(std::env::vars())
+    }
+
+    /// Loads and validates the configuration with the given environment variables.
+    pub fn load_with_env(
+        &self,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Config, ConfigError> {
+        let mut table = match &self.file {
+            Some(path) => read_file(path)?,
+            None => Table::new(),
+        };
+
+        for (name, value) in env {
+            let Some(name) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+
+            // Other variables with the prefix are used by the command line arguments.
+            let name = name.to_lowercase();
+            match name.split_once('_') {
+                Some((section, key)) if SECTIONS.contains(&section) => {
+                    set(&mut table, &format!("{section}.{key}"), &value)?
+                }
+                _ => {}
+            }
+        }
+
+        for (key, value) in &self.overrides {
+            set(&mut table, key, value)?;
+        }
+
+        let config = Config::deserialize(Value::Table(table)).map_err(ConfigError::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+fn read_file(path: &Path) -> Result<Table, ConfigError> {
+    let content = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+        path: path.to_owned(),
+        source,
+    })?;
+
+    toml::from_str(&content).map_err(ConfigError::Parse)
+}
+
+/// Sets the value of the given `section.key`. Values are parsed as TOML, and used as plain
+/// strings if they aren't valid TOML (so that `5s` doesn't need to be quoted).
+fn set(table: &mut Table, key: &str, value: &str) -> Result<(), ConfigError> {
+    let Some((section, key)) = key.split_once('.') else {
+        return Err(ConfigError::InvalidKey(key.to_owned()));
+    };
+
+    let value = toml::from_str::<Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut parsed| parsed.remove("value"))
+        .unwrap_or_else(|| Value::String(value.to_owned()));
+
+    match table
+        .entry(section)
+        .or_insert_with(|| Value::Table(Table::new()))
+    {
+        Value::Table(section) => {
+            section.insert(key.to_owned(), value);
+            Ok(())
+        }
+        _ => Err(ConfigError::InvalidKey(format!("{section}.{key}"))),
+    }
+}
+
+/// Reloads the configuration every time the process receives SIGHUP, updating the settings.
+/// Values that can't be changed whilst the server is running are left untouched, and an invalid
+/// configuration is ignored altogether.
+pub async fn reload_on_hangup(
+    sources: ConfigSources,
+    server: ServerConfig,
+    settings: Arc<watch::Sender<Settings>>,
+) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(error) => {
+                error!("Could not listen for SIGHUP, the configuration won't be reloaded: {error}");
+                return std::future::pending().await;
+            }
+        };
+
+        while hangups.recv().await.is_some() {
+            let config = match sources.load() {
+                Ok(config) => config,
+                Err(error) => {
+                    error!("Could not reload the configuration, keeping the current one: {error}");
+                    continue;
+                }
+            };
+
+            if config.server != server {
+                warn!("Changes to the [server] section only apply once the server restarts.");
+            }
+
+            settings.send_replace(config.settings());
+            info!("Reloaded the configuration.");
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = (sources, server, settings);
+        std::future::pending().await
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("could not read configuration file {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("invalid configuration: {0}")]
+    Parse(toml::de::Error),
+
+    #[error("invalid configuration key {0}, expected section.key")]
+    InvalidKey(String),
+
+    #[error("invalid configuration: {0}")]
+    Invalid(String),
+}
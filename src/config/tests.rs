@@ -0,0 +1,111 @@
+#![cfg(test)]
+
+use std::net::Ipv6Addr;
+
+use super::*;
+
+fn env(variables: &[(&str, &str)]) -> Vec<(String, String)> {
+    variables
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn defaults_are_valid() {
+    let config = ConfigSources::default().load_with_env([]).unwrap();
+
+    assert_eq!(config, Config::default());
+    assert_eq!(config.matchmaking.starting_elo, 1000);
+}
+
+#[test]
+fn layers_override_each_other() {
+    let directory = std::env::temp_dir().join("match-server-config-layers");
+    std::fs::create_dir_all(&directory).unwrap();
+    let file = directory.join("config.toml");
+    std::fs::write(
+        &file,
+        r#"
+        [server]
+        addresses = ["::1", "127.0.0.1"]
+        port = 8000
+
+        [matches]
+        move_timeout = "2s"
+        query_retries = 5
+        "#,
+    )
+    .unwrap();
+
+    let sources = ConfigSources {
+        file: Some(file),
+        overrides: vec![
+            ("server.port".to_owned(), "9000".to_owned()),
+            (
+                "server.tls".to_owned(),
+                r#"{ certificate = "cert.pem", key = "key.pem" }"#.to_owned(),
+            ),
+        ],
+    };
+    let config = sources
+        .load_with_env(env(&[
+            ("MANCALA_MATCHES_MOVE_TIMEOUT", "500ms"),
+            ("MANCALA_SERVER_PORT", "8500"),
+            // Used by the command line arguments, not the configuration.
+            ("MANCALA_DATABASE", "bots.db"),
+            ("HOME", "/root"),
+        ]))
+        .unwrap();
+
+    assert_eq!(
+        config.server.addresses,
+        [
+            IpAddr::from(Ipv6Addr::LOCALHOST),
+            Ipv4Addr::LOCALHOST.into()
+        ]
+    );
+    assert_eq!(config.server.port, Some(9000));
+    assert_eq!(config.matches.move_timeout, Duration::from_millis(500));
+    assert_eq!(config.matches.query_retries, 5);
+    assert_eq!(config.matches.connection_retries, 8);
+    assert_eq!(
+        config.server.tls.map(|tls| tls.certificate),
+        Some(PathBuf::from("cert.pem"))
+    );
+}
+
+#[test]
+fn rejects_invalid_configurations() {
+    let load = |overrides: &[(&str, &str)]| {
+        ConfigSources {
+            file: None,
+            overrides: env(overrides),
+        }
+        .load_with_env([])
+    };
+
+    assert!(matches!(
+        load(&[("presence.pong_timeout", "20s")]),
+        Err(ConfigError::Invalid(_))
+    ));
+    assert!(matches!(
+        load(&[("matches.query_retries", "0")]),
+        Err(ConfigError::Invalid(_))
+    ));
+    let too_many_moves = (MAX_RANDOM_MOVES + 1).to_string();
+    match load(&[("matches.opening_moves", &too_many_moves)]) {
+        Err(ConfigError::Invalid(message)) => {
+            assert!(message.ends_with(&format!("at most {MAX_RANDOM_MOVES}")))
+        }
+        _ => panic!("more opening moves than can be played were accepted"),
+    }
+    assert!(matches!(
+        load(&[("matches.unknown", "1")]),
+        Err(ConfigError::Parse(_))
+    ));
+    assert!(matches!(
+        load(&[("port", "1")]),
+        Err(ConfigError::InvalidKey(_))
+    ));
+}
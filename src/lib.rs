@@ -1,3 +1,4 @@
+pub mod config;
 pub mod mancala;
 pub mod matchmaker;
 pub mod server;
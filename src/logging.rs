@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+use color_eyre::{eyre::WrapErr, Result as EyreResult};
+use tracing_appender::{non_blocking::WorkerGuard, rolling};
+use tracing_subscriber::{
+    filter::LevelFilter,
+    fmt::{self, MakeWriter},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+    Layer, Registry,
+};
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum LogFormat {
+    /// Human readable lines.
+    Text,
+
+    /// One JSON object per line, including the spans (such as the match and its bots) the
+    /// event happened in.
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum LogRotation {
+    Hourly,
+    Daily,
+    Never,
+}
+
+/// Logs to the standard output, and to rotating files in the given directory if there is one.
+/// The returned guard must be kept alive for as long as logs should be written to the files.
+pub fn init(
+    level: tracing::Level,
+    format: LogFormat,
+    directory: Option<&Path>,
+    rotation: LogRotation,
+) -> EyreResult<Option<WorkerGuard>> {
+    let mut layers = vec![layer(format, std::io::stdout, true)];
+
+    let guard = match directory {
+        Some(directory) => {
+            let rotation = match rotation {
+                LogRotation::Hourly => rolling::Rotation::HOURLY,
+                LogRotation::Daily => rolling::Rotation::DAILY,
+                LogRotation::Never => rolling::Rotation::NEVER,
+            };
+            let appender = rolling::Builder::new()
+                .rotation(rotation)
+                .filename_prefix("match-server")
+                .filename_suffix("log")
+                .build(directory)
+                .wrap_err_with(|| format!("Could not log to {}", directory.display()))?;
+
+            // Writing to files happens on a dedicated thread so that it never stalls the server.
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            layers.push(layer(format, writer, false));
+            Some(guard)
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(LevelFilter::from_level(level))
+        .init();
+
+    Ok(guard)
+}
+
+fn layer<W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = fmt::layer().with_writer(writer).with_ansi(ansi);
+
+    match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    }
+}
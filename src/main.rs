@@ -1,18 +1,31 @@
-use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
 
 use match_server::{
-    matchmaker::run_matches,
-    server::{self, app_state::AppState},
+    config::{self, ConfigSources, ENV_PREFIX},
+    matchmaker::supervise_matches,
+    server::{
+        self,
+        app_state::AppState,
+        database::{open_database, Database},
+        listen,
+        presence::run_heartbeats,
+        shutdown,
+        storage::{memory::MemoryStorage, Storage},
+    },
 };
 
-use axum::Router;
+use axum::{routing::get, Router};
 use clap::Parser;
-use tokio::net::TcpListener;
 use tower_http::trace::TraceLayer;
 
-use color_eyre::{eyre::WrapErr, Result as EyreResult};
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Result as EyreResult,
+};
 
 mod cli;
+mod commands;
+mod logging;
 
 #[tokio::main]
 async fn main() -> EyreResult<()> {
@@ -24,25 +37,69 @@ async fn main() -> EyreResult<()> {
     // not setup these crates should an error accur whilst parsing arguments as that would just be
     // a waste of time).
     color_eyre::install()?;
-    tracing_subscriber::fmt().with_max_level(args.log).init();
+    let _log_guard = logging::init(
+        args.log,
+        args.log_format,
+        args.log_directory.as_deref(),
+        args.log_rotation,
+    )?;
+
+    let sources = ConfigSources {
+        file: args.config.clone(),
+        overrides: args.config_overrides(),
+    };
+
+    if let Some(command) = args.command {
+        let database = args
+            .database
+            .ok_or_else(|| eyre!("A database must be given with --database"))?;
+        return commands::run(command, &database, &sources).await;
+    }
+
+    // Opening the database applies all pending migrations, so there is nothing left to do.
+    // (clap guarantees a database path is given with --migrate-only).
+    if args.migrate_only {
+        if let Some(database) = &args.database {
+            open_database(database).wrap_err("Could not migrate the database")?;
+        }
+        return Ok(());
+    }
+
+    // The configuration is validated before anything else is done, so that mistakes are
+    // caught right away.
+    let config = sources
+        .load()
+        .wrap_err("Could not load the configuration")?;
+
+    let Some(port) = config.server.port else {
+        return Err(eyre!(
+            "A port must be given with --port, {ENV_PREFIX}SERVER_PORT or in the configuration file"
+        ));
+    };
 
     // The app state contains all of the data for the application. It is trivialy cloneable,
     // as all of it's data is in Arcs or other smart pointers. This cloneability is needed for
     // axum and the matchmaker.
-    let state = AppState::new(&args.database);
+    let storage: Arc<dyn Storage> = match &args.database {
+        Some(database) => Arc::new(
+            Database::open_with_connections(database, config.server.max_connections)
+                .wrap_err_with(|| format!("Could not load in database {}", database.display()))?,
+        ),
+        None => Arc::new(MemoryStorage::default()),
+    };
+    let state = AppState::new(storage, config.settings());
 
     // We are using TCP instead of UDP even if we consider the network to be reliable (and in the
     // offchance it isn't, there should be enough guardrails to prevent undesireable behavior)
     // because axum works better with TCP than it does with UDP. Regardless, the messages sent
     // are fairly small, and overall this **should** not be a bottleneck.
-    let address = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), args.port);
-
-    let listener = TcpListener::bind(address)
-        .await
-        .wrap_err_with(|| format!("Could not bind the server to address {address}"))?;
+    let listeners = listen::bind(&config.server, port).await?;
 
     // Create all the routes for the server.
     let routes = Router::new()
+        .route("/metrics", get(server::metrics::serve_metrics))
+        .route("/healthz", get(server::health::healthz))
+        .route("/readyz", get(server::health::readyz))
         // The state must be placed AFTER all the current router's routes but BEFORE the nested
         // router's routes, as those may have a completely different state.
         .with_state(state.clone())
@@ -55,14 +112,22 @@ async fn main() -> EyreResult<()> {
         // top)
         .layer(TraceLayer::new_for_http());
 
+    let mut server = tokio::spawn(listen::serve(listeners, routes, state.clone()));
+
     // Whichever async function returns first will force the other to do so too. In theory, neither
     // one of these functions should ever return, but in the off chance one does, we can assume it
     // to be because of some error, and given all branches depend on one another, the end of one
     // branch should result in the end of all branches.
     tokio::select! {
-        _ = run_matches(state) => {},
-        result = axum::serve(listener, routes.into_make_service_with_connect_info::<SocketAddr>()) => result?
+        _ = supervise_matches(state.clone()) => {},
+        _ = run_heartbeats(state.clone()) => {},
+        _ = config::reload_on_hangup(sources, config.server.clone(), state.settings.clone()) => {},
+        result = &mut server => return Ok(result??),
+        _ = shutdown::signal() => {},
     }
 
+    shutdown::shut_down(&state).await;
+    server.await??;
+
     Ok(())
 }
@@ -0,0 +1,61 @@
+use rand::seq::IndexedRandom;
+use serde::{Deserialize, Serialize};
+
+use super::Game;
+
+/// Bots played by the server itself, which developers can practice against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuiltinBot {
+    /// Plays any legal move.
+    Random,
+    /// Plays the move that scores the most seeds right away, preferring moves that earn it
+    /// another turn.
+    Greedy,
+}
+
+impl BuiltinBot {
+    pub const ALL: [Self; 2] = [Self::Random, Self::Greedy];
+
+    /// Name of the bot, as chosen at login and shown to spectators.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Random => "random",
+            Self::Greedy => "greedy",
+        }
+    }
+
+    /// Inverse of [`BuiltinBot::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|bot| bot.name() == name)
+    }
+
+    /// Whether the bot trades sides when the pie rule allows it. The greedy bot does so when the
+    /// opponent's first move scored.
+    pub fn wants_swap(self, game: &Game, player: usize) -> bool {
+        match self {
+            Self::Random => rand::random(),
+            Self::Greedy => game.points[1 - player] > game.points[player],
+        }
+    }
+
+    /// Picks the cell to play for the given player. The game must not be finished.
+    pub fn choose(self, game: &Game, player: usize) -> u8 {
+        let moves: Vec<u8> = (0..12)
+            .filter(|&cell| game.is_move_valid(player as u8, cell))
+            .collect();
+        debug_assert!(!moves.is_empty());
+
+        match self {
+            Self::Random => *moves.choose(&mut rand::rng()).unwrap_or(&0),
+            Self::Greedy => moves
+                .into_iter()
+                .max_by_key(|&cell| {
+                    let mut next = game.clone();
+                    let next_player = next.play(player, cell as usize);
+                    (next.points[player], next_player == player)
+                })
+                .unwrap_or(0),
+        }
+    }
+}
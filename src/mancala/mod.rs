@@ -2,17 +2,25 @@ use std::ops::{Deref, DerefMut};
 
 use serde::Serialize;
 
+pub mod builtin;
+pub mod opening;
 pub mod play_match;
 mod tests;
 
+/// Amount of pits on each side of the board.
+pub const PITS: usize = 6;
+
+/// Amount of seeds in each pit at the start of a game.
+pub const INITIAL_SEEDS: u8 = 4;
+
 #[derive(Clone, Copy, Serialize, Debug, PartialEq)]
 #[repr(align(8))]
-pub struct Board([u8; 6]);
+pub struct Board([u8; PITS]);
 
 impl Default for Board {
     #[inline]
     fn default() -> Self {
-        Self([4; 6])
+        Self([INITIAL_SEEDS; PITS])
     }
 }
 
@@ -50,7 +58,16 @@ impl From<Board> for u64 {
     }
 }
 
-#[derive(Default, Debug, Clone)]
+/// What a player does on its turn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Move {
+    /// Sows the seeds of the given cell.
+    Sow(u8),
+    /// Trades sides with the opponent, as allowed by the pie rule.
+    Swap,
+}
+
+#[derive(Default, Debug, Clone, Serialize)]
 pub struct Game {
     boards: [Board; 2],
     points: [u8; 2],
@@ -59,13 +76,8 @@ pub struct Game {
 impl Game {
     #[inline]
     pub fn is_finished(&self) -> bool {
-        // Code is optimized for performance, but might break if we
-        // change the board struct.
-        let mut buffer = [0u8; 16];
-        buffer[..12].copy_from_slice(unsafe {
-            std::slice::from_raw_parts(self.boards.as_ptr() as *const u8, 12)
-        });
-        u128::from_ne_bytes(buffer) == 0
+        // Boards are padded to 8 bytes, so they can't be read as a single 12 bytes slice.
+        self.boards[0].is_empty() && self.boards[1].is_empty()
     }
 
     #[inline]
@@ -83,6 +95,12 @@ impl Game {
         }
     }
 
+    /// Trades the sides of the players, seeds they scored included, as done by the pie rule.
+    pub fn swap(&mut self) {
+        self.boards.swap(0, 1);
+        self.points.swap(0, 1);
+    }
+
     pub fn play(&mut self, player: usize, cell: usize) -> usize {
         // There are only two players, so we must be sure the player index is 0 or 1.
         debug_assert!(player < 2);
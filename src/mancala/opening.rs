@@ -0,0 +1,90 @@
+use rand::{seq::IndexedRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use super::{builtin::BuiltinBot, Board, Game, INITIAL_SEEDS, PITS};
+
+/// Most random moves an opening can start with, as too many of them could end the game.
+pub const MAX_RANDOM_MOVES: u8 = 16;
+
+/// Sides of balanced starting positions, each of them given to both players (so that the
+/// position is the same from either side of the board).
+const BOOK: [[u8; PITS]; 8] = [
+    [4, 4, 4, 4, 4, 4],
+    [3, 5, 4, 4, 5, 3],
+    [5, 3, 4, 4, 3, 5],
+    [2, 4, 6, 6, 4, 2],
+    [6, 4, 2, 2, 4, 6],
+    [4, 3, 5, 5, 3, 4],
+    [3, 4, 5, 5, 4, 3],
+    [5, 4, 3, 3, 4, 5],
+];
+
+/// How the starting position of each match is picked. As none of them is fair to both seats on
+/// its own, series play each opening twice, swapping seats in between.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Openings {
+    /// Every pit starts with the same amount of seeds.
+    #[default]
+    Standard,
+    /// A position from a set of balanced ones.
+    Book,
+    /// The standard position, followed by a few random moves.
+    RandomMoves,
+    /// The seeds are spread randomly (at least one per pit), both sides getting the same
+    /// distribution.
+    RandomSeeds,
+}
+
+/// Position a match starts from.
+#[derive(Clone, Debug, Default)]
+pub struct Opening {
+    pub game: Game,
+    /// The player to move first, which may not be the first one once random moves were played.
+    pub player: usize,
+}
+
+impl Openings {
+    /// Picks a starting position, playing `random_moves` moves if the openings are random moves.
+    pub fn generate(self, random_moves: u8) -> Opening {
+        let mut rng = rand::rng();
+
+        match self {
+            Self::Standard => Opening::default(),
+            Self::Book => Opening::mirrored(*BOOK.choose(&mut rng).unwrap_or(&BOOK[0])),
+            Self::RandomMoves => loop {
+                let mut opening = Opening::default();
+                for _ in 0..random_moves {
+                    let cell = BuiltinBot::Random.choose(&opening.game, opening.player);
+                    opening.player = opening.game.play(opening.player, cell as usize);
+                }
+
+                // Games that end before they even started are no good.
+                if !opening.game.is_finished() {
+                    break opening;
+                }
+            },
+            Self::RandomSeeds => {
+                let mut side = [1; PITS];
+                for _ in 0..PITS as u8 * (INITIAL_SEEDS - 1) {
+                    side[rng.random_range(0..PITS)] += 1;
+                }
+
+                Opening::mirrored(side)
+            }
+        }
+    }
+}
+
+impl Opening {
+    /// Position in which both players have the given side.
+    fn mirrored(side: [u8; PITS]) -> Self {
+        Self {
+            game: Game {
+                boards: [Board(side), Board(side)],
+                points: [0, 0],
+            },
+            player: 0,
+        }
+    }
+}
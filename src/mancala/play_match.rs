@@ -1,77 +1,88 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::Message;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tokio::sync::Mutex;
-use tracing::error;
+use tokio::sync::watch;
+use tracing::{error, instrument, trace};
 
-use super::{Board, Game};
+use crate::{config::MatchSettings, server::bot_socket::BotSocket};
 
-/// Plays an entire match of mancala between two players, and returns information about
-/// who won (if anyone won), in what manner and by how much.
-pub async fn play_match(players: impl Into<[Arc<Mutex<WebSocket>>; 2]>) -> Winner {
-    let players = players.into();
+use super::{builtin::BuiltinBot, opening::Opening, Board, Game, Move};
 
-    let mut game = Game::default();
-    let mut current_player = 0;
+/// Version of the protocol bots use to play, bumped whenever it changes in an incompatible way.
+pub const PROTOCOL_VERSION: u32 = 3;
 
-    while !game.is_finished() {
-        const CONNECTION_RETRY_COUNT: u8 = 8;
-        const QUERY_RETRY_COUNT: u8 = 2;
-
-        let mut connection_retries = CONNECTION_RETRY_COUNT;
-        let mut querying_retries = QUERY_RETRY_COUNT;
-        let player_move = loop {
-            match game
-                .send_to_player(current_player, players[current_player].clone())
-                .await
-            {
-                Ok(response) => {
-                    let response = response.value;
-                    if game.is_move_valid(current_player as u8, response) {
-                        break response;
-                    }
-                    querying_retries -= 1;
-                }
+/// How moves are obtained from a player during a match.
+#[derive(Clone)]
+pub enum PlayerConnection {
+    /// A bot connected to the server, which is queried over its socket.
+    Remote(RemotePlayer),
+    /// A bot played by the server itself.
+    Builtin(BuiltinBot),
+}
 
-                // Err(PlayerResponseError::RequestError(e)) => {
-                //     trace!("Request Error: {e}");
-                //
-                //     connection_retries -= 1;
-                //     if connection_retries == 0 {
-                //         return Winner::ByDisqualification(1 - current_player as u8, true);
-                //     }
-                // }
-                Err(PlayerResponseError::InvalidResponse) => {
-                    // We managed to connect to the player, so might as well give
-                    // them the benefit of the doubt
-                    connection_retries = QUERY_RETRY_COUNT;
-
-                    querying_retries -= 1;
-
-                    if querying_retries == 0 {
-                        return Winner::ByDisqualification(1 - current_player as u8, false);
-                    }
-                }
+/// Everything needed to talk to a remote player during a match.
+#[derive(Clone)]
+pub struct RemotePlayer {
+    /// The player's socket, to which a new web socket is attached when the player reconnects.
+    pub socket: Arc<BotSocket>,
 
-                Err(PlayerResponseError::CouldNotSerialize(error)) => {
-                    error!("Could not serialize the the board to send it to the player due to following error: \"{error}\", aborting instead and resoliving match in a tie.");
-                    return Winner::Tie;
-                }
+    /// Notified every time the player reconnects and its socket is swapped.
+    pub reconnections: watch::Receiver<u64>,
+}
+
+/// Plays an entire match of mancala between two players from the given opening, and returns
+/// information about who won (if anyone won), in what manner and by how much. The given function
+/// is called after each move with the player that moved, its move and the resulting game.
+#[instrument(skip_all)]
+pub async fn play_match(
+    players: impl Into<[PlayerConnection; 2]>,
+    settings: MatchSettings,
+    opening: Opening,
+    mut on_move: impl FnMut(u8, Move, &Game),
+) -> Winner {
+    let mut players = players.into();
+
+    let Opening {
+        mut game,
+        player: mut current_player,
+    } = opening;
 
-                Err(PlayerResponseError::SendFailed(_))
-                | Err(PlayerResponseError::ReceiveFailed(_))
-                | Err(PlayerResponseError::DidNotReceiveResponse) => {
-                    connection_retries -= 1;
-                    if connection_retries == 0 {
-                        return Winner::ByDisqualification(1 - current_player as u8, true);
-                    }
+    // With the pie rule, the player moving second may swap on its first turn.
+    let first_player = current_player;
+    let mut swap_available = settings.pie_rule;
+
+    while !game.is_finished() {
+        let can_swap = swap_available && current_player != first_player;
+        if can_swap {
+            swap_available = false;
+        }
+
+        let player_move = match &mut players[current_player] {
+            PlayerConnection::Builtin(bot) if can_swap && bot.wants_swap(&game, current_player) => {
+                Move::Swap
+            }
+            PlayerConnection::Builtin(bot) => Move::Sow(bot.choose(&game, current_player)),
+            PlayerConnection::Remote(remote) => {
+                match query_player(&game, current_player, can_swap, remote, &settings).await {
+                    Ok(player_move) => player_move,
+                    Err(winner) => return winner,
                 }
             }
         };
 
-        current_player = game.play(current_player, player_move as usize);
+        let player = current_player;
+        trace!("Player {player} played {player_move:?}.");
+        current_player = match player_move {
+            Move::Sow(cell) => game.play(current_player, cell as usize),
+            // Swapping takes the player's turn, so the opponent moves again, from its new side.
+            Move::Swap => {
+                game.swap();
+                1 - current_player
+            }
+        };
+        on_move(player as u8, player_move, &game);
     }
 
     if game.points[0] == game.points[1] {
@@ -89,12 +100,121 @@ pub async fn play_match(players: impl Into<[Arc<Mutex<WebSocket>>; 2]>) -> Winne
     }
 }
 
+/// Queries the player until it sends a valid move, which is returned. Should the player be
+/// disqualified (or the match be unable to continue), the match's result is returned instead.
+async fn query_player(
+    game: &Game,
+    current_player: usize,
+    can_swap: bool,
+    player: &mut RemotePlayer,
+    settings: &MatchSettings,
+) -> Result<Move, Winner> {
+    let mut connection_retries = settings.connection_retries;
+    let mut querying_retries = settings.query_retries;
+    loop {
+        let socket = player.socket.clone();
+        let (reason, message) = match game
+            .send_to_player(
+                current_player,
+                can_swap,
+                socket.clone(),
+                settings.move_timeout,
+            )
+            .await
+        {
+            Ok(PlayerResponse { swap: true, .. }) if can_swap => return Ok(Move::Swap),
+            Ok(PlayerResponse { swap: true, .. }) => (
+                DisqualificationReason::IllegalMove,
+                "swapping is only allowed on the first turn of the second player, with the pie \
+                 rule"
+                    .to_owned(),
+            ),
+            Ok(PlayerResponse {
+                value: Some(cell), ..
+            }) if game.is_move_valid(current_player as u8, cell) => {
+                return Ok(Move::Sow(cell));
+            }
+            Ok(PlayerResponse {
+                value: Some(cell), ..
+            }) => (
+                DisqualificationReason::IllegalMove,
+                format!("cell {cell} can't be played"),
+            ),
+            Ok(PlayerResponse { value: None, .. }) => (
+                DisqualificationReason::MalformedResponse,
+                "the move must either have a value or swap".to_owned(),
+            ),
+
+            Err(PlayerResponseError::InvalidResponse) => (
+                DisqualificationReason::MalformedResponse,
+                "moves must be sent as text messages".to_owned(),
+            ),
+            Err(PlayerResponseError::MalformedResponse(error)) => (
+                DisqualificationReason::MalformedResponse,
+                format!("could not parse the move: {error}"),
+            ),
+
+            // Bots are expected to answer in time, however slow they are.
+            Err(PlayerResponseError::TimedOut) => {
+                let reason = DisqualificationReason::TimedOut;
+                let message = format!("no move was received within {:?}", settings.move_timeout);
+                reject(&socket, reason, message, 0).await;
+                return Err(Winner::ByDisqualification(1 - current_player as u8, reason));
+            }
+
+            Err(PlayerResponseError::CouldNotSerialize(error)) => {
+                error!("Could not serialize the the board to send it to the player due to following error: \"{error}\", aborting instead and resoliving match in a tie.");
+                return Err(Winner::Tie);
+            }
+
+            Err(PlayerResponseError::SendFailed(_))
+            | Err(PlayerResponseError::DidNotReceiveResponse)
+            | Err(PlayerResponseError::Unreachable) => {
+                let disconnected = Winner::ByDisqualification(
+                    1 - current_player as u8,
+                    DisqualificationReason::Disconnected,
+                );
+
+                connection_retries = connection_retries.saturating_sub(1);
+                if connection_retries == 0 {
+                    return Err(disconnected);
+                }
+
+                // Retrying right away would most likely fail again, so the player is given
+                // some time to reconnect first. The query (and thus the current position)
+                // is sent again on the new connection.
+                let reconnections = &mut player.reconnections;
+                match tokio::time::timeout(settings.reconnection_grace, reconnections.changed())
+                    .await
+                {
+                    Ok(Ok(())) => trace!("Player {current_player} reconnected mid-match."),
+                    _ => return Err(disconnected),
+                }
+                continue;
+            }
+        };
+
+        // We managed to talk to the player, so might as well give them the benefit of the
+        // doubt when it comes to their connection.
+        connection_retries = settings.connection_retries;
+
+        querying_retries = querying_retries.saturating_sub(1);
+        trace!("Rejected the move of player {current_player}: {message}.");
+        reject(&socket, reason, message, querying_retries).await;
+        if querying_retries == 0 {
+            return Err(Winner::ByDisqualification(1 - current_player as u8, reason));
+        }
+    }
+}
+
 /// Summarizes the end of a mancala match between two bots.
 pub enum Winner {
     /// One of the bots was unable to communicate with the server either
     /// because it has disconnected, or because it was unable to send back
-    /// appropriate data. Thus the other bot won by disqualification.
-    ByDisqualification(u8, bool),
+    /// appropriate data. Thus the other bot won by disqualification. The first
+    /// parameter describes which player won, and the second why the other one
+    /// was disqualified.
+    ByDisqualification(u8, DisqualificationReason),
 
     /// Both bots played correctly until the end of the game, but one played
     /// better than the other. The first paramter describes which player won
@@ -107,73 +227,163 @@ pub enum Winner {
     Tie,
 }
 
+/// Why a bot was disqualified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisqualificationReason {
+    /// The bot kept playing cells it wasn't allowed to.
+    IllegalMove,
+    /// The bot kept sending answers that weren't moves.
+    MalformedResponse,
+    /// The bot did not answer in time.
+    TimedOut,
+    /// The bot could not be reached, even after being given time to reconnect.
+    Disconnected,
+}
+
+impl DisqualificationReason {
+    /// Name of the reason, as it is stored and sent to bots.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::IllegalMove => "illegal_move",
+            Self::MalformedResponse => "malformed_response",
+            Self::TimedOut => "timed_out",
+            Self::Disconnected => "disconnected",
+        }
+    }
+
+    /// Inverse of [`DisqualificationReason::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::IllegalMove,
+            Self::MalformedResponse,
+            Self::TimedOut,
+            Self::Disconnected,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.name() == name)
+    }
+}
+
+/// Sent to a player whose answer was rejected, right before it is queried again (or
+/// disqualified, once it has no attempts left).
+#[derive(Serialize)]
+struct Rejection {
+    error: DisqualificationReason,
+    message: String,
+    remaining_attempts: u8,
+}
+
+/// Tells the player why its answer was rejected. The player may well be gone already, in which
+/// case the next query notices it.
+async fn reject(
+    socket: &BotSocket,
+    reason: DisqualificationReason,
+    message: String,
+    remaining_attempts: u8,
+) {
+    let rejection = Rejection {
+        error: reason,
+        message,
+        remaining_attempts,
+    };
+    let Ok(serialized) = serde_json::to_string(&rejection) else {
+        return;
+    };
+
+    let _ = socket.send(serialized.into());
+}
+
 impl Game {
-    fn to_json(&self, player: usize) -> Result<String, PlayerResponseError> {
+    fn to_json(&self, player: usize, can_swap: bool) -> Result<String, PlayerResponseError> {
         debug_assert!(player < 2);
 
         #[derive(Serialize)]
         struct SerializableGame {
             boards: [Board; 2],
             points: [u8; 2],
+            /// Whether the player may answer with a swap instead of a move (see the pie rule).
+            can_swap: bool,
         }
 
         Ok(serde_json::to_string(&SerializableGame {
             boards: [self.boards[player], self.boards[1 - player]],
             points: [self.points[player], self.points[1 - player]],
+            can_swap,
         })?)
     }
 
     async fn send_to_player(
         &self,
         player: usize,
-        socket: Arc<Mutex<WebSocket>>,
+        can_swap: bool,
+        socket: Arc<BotSocket>,
+        timeout: Duration,
     ) -> Result<PlayerResponse, PlayerResponseError> {
         debug_assert!(player < 2);
 
-        let serialized = self.to_json(player)?;
+        let serialized = self.to_json(player, can_swap)?;
 
-        let mut socket = socket.lock().await;
+        let mut exchange = socket.exchange().await;
+        let pongs = socket.pongs();
 
-        socket
+        exchange
             .send(serialized.into())
-            .await
             .map_err(|e| PlayerResponseError::SendFailed(e.into()))?;
 
-        let response = socket
-            .recv()
-            .await
-            .map(|e| e.map_err(|e| PlayerResponseError::ReceiveFailed(e.into())))
-            .ok_or(PlayerResponseError::DidNotReceiveResponse)??;
+        // The player is pinged alongside the query, so that a connection which silently dropped
+        // (and thus never answers) is told apart from a player that is merely too slow.
+        let _ = socket.send(Message::Ping(Default::default()));
+
+        // Pings and pongs are handled by the task driving the socket, so they never show up here.
+        let response = match tokio::time::timeout(timeout, exchange.recv()).await {
+            Ok(response) => response.ok_or(PlayerResponseError::DidNotReceiveResponse)?,
+            Err(_) if pongs.has_changed().unwrap_or(false) => {
+                return Err(PlayerResponseError::TimedOut)
+            }
+            Err(_) => return Err(PlayerResponseError::Unreachable),
+        };
 
-        Ok(match response {
-            Message::Text(text) => serde_json::from_str::<PlayerResponse>(&text),
+        match response {
+            Message::Text(text) => serde_json::from_str::<PlayerResponse>(&text)
+                .map_err(PlayerResponseError::MalformedResponse),
             Message::Binary(_) | Message::Ping(_) | Message::Pong(_) => {
-                return Err(PlayerResponseError::InvalidResponse)
+                Err(PlayerResponseError::InvalidResponse)
             }
-            Message::Close(_close_frame) => todo!("handle client closing the web socket"),
-        }?)
+            // The player is gone, which is no different from the socket having been dropped.
+            Message::Close(_close_frame) => Err(PlayerResponseError::DidNotReceiveResponse),
+        }
     }
 }
 
+/// A player's answer to a query: either the cell it plays, or a swap when allowed.
 #[derive(Deserialize)]
 pub struct PlayerResponse {
-    pub value: u8,
+    pub value: Option<u8>,
+    #[serde(default)]
+    pub swap: bool,
 }
 
 #[derive(Error, Debug)]
 enum PlayerResponseError {
     #[error("could not send information to the player due to following error: {0}")]
-    SendFailed(Box<dyn std::error::Error>),
-
-    #[error("failed to retreive data from player due to following error: {0}")]
-    ReceiveFailed(Box<dyn std::error::Error>),
+    SendFailed(Box<dyn std::error::Error + Send + Sync>),
 
     #[error("did not receive a response from the player")]
     DidNotReceiveResponse,
 
+    #[error("the player took too long to respond")]
+    TimedOut,
+
+    #[error("the player neither answered nor showed any sign of life")]
+    Unreachable,
+
     #[error("invalid response from player")]
     InvalidResponse,
 
+    #[error("could not parse the player's response: {0}")]
+    MalformedResponse(serde_json::Error),
+
     #[error("failed to serialize board due to error: {0}")]
     CouldNotSerialize(#[from] serde_json::Error),
 }
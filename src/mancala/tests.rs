@@ -173,3 +173,100 @@ fn dont_take_stone() {
         assert_eq!(game.points[i], 1);
     }
 }
+
+#[test]
+fn builtin_bots_play_valid_moves() {
+    for bot in builtin::BuiltinBot::ALL {
+        let mut game = Game::default();
+        let mut player = 0;
+        for _ in 0..200 {
+            if game.is_finished() {
+                break;
+            }
+
+            let cell = bot.choose(&game, player);
+            assert!(game.is_move_valid(player as u8, cell));
+            player = game.play(player, cell as usize);
+        }
+    }
+}
+
+#[test]
+fn greedy_bot_scores() {
+    // Sowing the third cell is the only opening move that ends in the store, earning a point and
+    // another turn.
+    let game = Game::default();
+    assert_eq!(builtin::BuiltinBot::Greedy.choose(&game, 0), 2);
+}
+
+#[test]
+fn finished_only_once_both_boards_are_empty() {
+    let mut game = Game {
+        boards: [Board([0; PITS]), Board([0, 0, 0, 0, 0, 1])],
+        points: [24, 23],
+    };
+    assert!(!game.is_finished());
+
+    game.boards[1].0[5] = 0;
+    assert!(game.is_finished());
+}
+
+#[test]
+fn openings_are_playable() {
+    let openings = [
+        opening::Openings::Standard,
+        opening::Openings::Book,
+        opening::Openings::RandomMoves,
+        opening::Openings::RandomSeeds,
+    ];
+    for openings in openings {
+        for _ in 0..20 {
+            let opening::Opening { game, player } = openings.generate(opening::MAX_RANDOM_MOVES);
+
+            let seeds: u32 = game
+                .boards
+                .iter()
+                .flat_map(|board| board.iter())
+                .chain(&game.points)
+                .map(|&seeds| seeds as u32)
+                .sum();
+            assert_eq!(seeds, 2 * PITS as u32 * INITIAL_SEEDS as u32);
+            assert!(!game.is_finished());
+            assert!(player < 2);
+        }
+    }
+}
+
+#[test]
+fn swap_trades_sides() {
+    let mut game = Game::default();
+    game.play(0, 2);
+    game.swap();
+
+    assert_eq!(game.boards[0], Board([4; PITS]));
+    assert_eq!(game.boards[1], Board([4, 4, 0, 5, 5, 5]));
+    assert_eq!(game.points, [0, 1]);
+}
+
+#[tokio::test]
+async fn pie_rule_lets_the_second_player_swap() {
+    let settings = crate::config::MatchSettings {
+        pie_rule: true,
+        ..Default::default()
+    };
+    let players = [builtin::BuiltinBot::Greedy; 2].map(play_match::PlayerConnection::Builtin);
+
+    let mut moves = Vec::new();
+    play_match::play_match(
+        players,
+        settings,
+        opening::Opening::default(),
+        |player, mv, _| moves.push((player, mv)),
+    )
+    .await;
+
+    // The greedy bot scores with its first move, so its opponent takes its side.
+    let swaps: Vec<_> = moves.iter().filter(|(_, mv)| *mv == Move::Swap).collect();
+    assert_eq!(swaps, [&(1, Move::Swap)]);
+    assert_eq!(moves.iter().position(|(_, mv)| *mv == Move::Swap), Some(2));
+}
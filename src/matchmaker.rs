@@ -1,128 +1,702 @@
-use std::time::Duration;
+use std::{
+    any::Any, collections::HashMap, panic::AssertUnwindSafe, sync::atomic::Ordering, time::Duration,
+};
 
-const WAIT_TIME: Duration = Duration::from_secs(1);
+use futures_util::FutureExt;
+use tokio::{task::JoinHandle, time::Instant};
 
-use tracing::{error, trace, warn};
+use tracing::{debug, error, instrument, trace, warn, Span};
 
 use crate::{
-    mancala::play_match::{play_match, Winner},
-    server::app_state::{AppState, Bot},
+    mancala::{
+        opening::Opening,
+        play_match::{play_match, DisqualificationReason, Winner},
+        Game, Move, INITIAL_SEEDS, PITS,
+    },
+    server::{
+        app_state::{AppState, Bot, Match, PracticeOpponent, Queue},
+        events::ServerEvent,
+        metrics::metrics,
+        presence::kick,
+        shutdown::ShutdownPhase,
+        spectators::SpectatorEvent,
+        storage::{unix_timestamp, MatchOutcome, NewMatch, NewPracticeMatch, StorageError},
+    },
 };
 
-pub async fn run_matches(state: AppState) {
-    trace!("Started the matchmaking task.");
+mod pairing;
+pub mod series;
+mod tests;
+
+use pairing::{Candidate, History};
+use series::{SeriesStop, SeriesSummary, SprtSettings};
+
+/// Elo a disqualified bot loses (and its opponent wins), as if it had lost by every seed on the
+/// board.
+const DISQUALIFICATION_DELTA: u8 = 2 * PITS as u8 * INITIAL_SEEDS;
+
+/// Whether a match counts towards the ladder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchKind {
+    /// Rated, and stored alongside the other matches of the ladder.
+    Ladder,
+    /// Unrated, and stored apart from the ladder's matches.
+    Practice,
+}
+
+/// Delay before restarting the matchmaker after it failed once, doubled after each consecutive
+/// failure.
+const MIN_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest delay before restarting the matchmaker. One that ran for longer than this before
+/// failing starts over from [`MIN_RESTART_DELAY`].
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
 
+/// Runs the matchmaker, restarting it whenever it panics (or stops, which it never should), so
+/// that a bug in matchmaking doesn't take the whole server down with it.
+pub async fn supervise_matches(state: AppState) {
     loop {
-        // We iterate through all of the pending bots whilst removing them from the pending
-        // bot array and have them matchmake with each other bot.
-
-        // We are dropping the lock to the pending bots early to avoid complications. (the cost
-        // of the copy can be considered minimal given the small (if not 0) size of the
-        // pending_bots array).
-        let pending_bots: Vec<_> = {
-            let mut lock = state.pending_bots.lock().await;
-            lock.drain(..).collect()
-        };
+        let started_at = Instant::now();
+        match AssertUnwindSafe(run_matches(state.clone()))
+            .catch_unwind()
+            .await
+        {
+            Ok(()) => error!("The matchmaker stopped unexpectedly."),
+            Err(panic) => error!("The matchmaker panicked: {}", panic_message(&*panic)),
+        }
 
-        // If there are no pending bots, there is no need to continue, so wait a bit and try it the
-        // next time around.
-        if pending_bots.is_empty() {
-            tokio::time::sleep(WAIT_TIME).await;
-            continue;
+        if started_at.elapsed() >= MAX_RESTART_DELAY {
+            state.matchmaker_failures.store(0, Ordering::Relaxed);
         }
+        let failures = state.matchmaker_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        let delay = restart_delay(failures);
 
-        // HACK: Unfortunately, I have not yet found a way to **not** perform a copy each time around
-        // whilst avoiding thread locking and other bugs. This should work for now, but should be
-        // looked at more seriously.
-        let other_bots: Vec<_> = state.connected_bots.lock().await.iter().cloned().collect();
-
-        for bot_a in pending_bots {
-            // We iterate through the whole list of current bots and setup the match between the
-            // current pending bot and the already registered ones.
-            for bot_b in other_bots.iter() {
-                // Each match is run async, as the better part of the time taken to run a match
-                // consists of HTTP communication and awaiting the bot's response.
-                // We are also running two matches, one where the first player is bot_a and one
-                // where the first player is bot_b for added fairness.
-                tokio::spawn(launch_match(state.clone(), bot_a.clone(), bot_b.clone()));
-                tokio::spawn(launch_match(state.clone(), bot_b.clone(), bot_a.clone()));
-            }
+        metrics().matchmaker_restarts.inc();
+        warn!("Restarting the matchmaker in {delay:?} ({failures} failure(s) in a row).");
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// How long to wait before restarting the matchmaker after the given amount of consecutive
+/// failures.
+fn restart_delay(failures: u32) -> Duration {
+    MIN_RESTART_DELAY
+        .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .min(MAX_RESTART_DELAY)
+}
+
+/// Extracts the message a task panicked with, which is either a `&str` or a `String`.
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+async fn run_matches(state: AppState) {
+    trace!("Started the matchmaking task.");
 
-            // We add the new bot after spawning all tokio tasks so as to not have the chance of
-            // a bot fighting against itself :p.
-            state.connected_bots.lock().await.insert(bot_a.clone());
+    // Pairings are remembered from one round to the next, and rebuilt from the stored matches
+    // whenever the matchmaker (re)starts.
+    let mut history = match state.storage.matches().await {
+        Ok(matches) => History::from_matches(&matches),
+        Err(error) => {
+            error!("Could not load the match history, starting from scratch: {error}");
+            History::default()
         }
+    };
+
+    loop {
+        metrics().matchmaking_rounds.inc();
+        *state.last_matchmaking_round_lock() = Some(Instant::now());
+
+        // Matches that start whilst shutting down would only be aborted.
+        if state.shutdown_phase() == ShutdownPhase::Running {
+            run_round(&state, &mut history).await;
+        }
+
         // Sleep for some time, as there is no need to run this code ad-nauseum given bots won't
         // connect frequently (and even if they do, them waiting a second for their matches to
         // start isn't the end of the world).
-        tokio::time::sleep(WAIT_TIME).await;
+        tokio::time::sleep(state.settings().matchmaking.interval).await;
     }
 }
 
-async fn launch_match(state: AppState, bot_a: Bot, bot_b: Bot) {
+/// Starts a match for every practicing bot whose opponent is available (bots only practice
+/// against each other when both asked to), then pairs every bot of the ladder that can play
+/// another match with the closest rated opponent available, see [`pairing::pair`].
+async fn run_round(state: &AppState, history: &mut History) {
+    let settings = state.settings().matchmaking;
+
+    // Newcomers join the ladder straight away, so that bots arriving together can play each
+    // other. The lock to the pending bots is dropped early to avoid complications.
+    let pending_bots: Vec<_> = state.pending_bots.lock().await.drain(..).collect();
+    let bots: HashMap<_, _> = {
+        let mut connected_bots = state.connected_bots.lock().await;
+        connected_bots.extend(pending_bots);
+        connected_bots
+            .iter()
+            .map(|bot| (bot.id, bot.clone()))
+            .collect()
+    };
+
+    let mut games = HashMap::<u16, usize>::new();
+    for running_match in state.running_matches_lock().values() {
+        for player in &running_match.players {
+            *games.entry(player.id).or_default() += 1;
+        }
+    }
+    let max_games = settings.max_concurrent_games as usize;
+    // Bots playing a series are left alone until it is over.
+    let series_bots = state.series_bots_lock().clone();
+    let is_free = |games: &HashMap<u16, usize>, bot: &Bot| {
+        games.get(&bot.id).copied().unwrap_or_default() < max_games
+            && !series_bots.contains(&bot.id)
+    };
+
+    for bot in bots.values() {
+        let Queue::Practice(opponent) = &bot.queue else {
+            continue;
+        };
+        if !is_free(&games, bot) {
+            continue;
+        }
+
+        let opponent = match opponent {
+            PracticeOpponent::Builtin(builtin) => Bot::builtin(*builtin),
+            PracticeOpponent::Bot(name) => {
+                // Bots of the ladder can't be made to practice (and thus play fewer rated
+                // matches), so the opponent must be practicing against this bot in return. Such
+                // pairs are then served once, from the bot with the lowest id.
+                let consents = |other: &Bot| {
+                    other.queue == Queue::Practice(PracticeOpponent::Bot(bot.name.clone()))
+                };
+                match bots.values().find(|other| other.name == *name) {
+                    Some(other)
+                        if other.id > bot.id && consents(other) && is_free(&games, other) =>
+                    {
+                        other.clone()
+                    }
+                    // The opponent may just not be logged in yet.
+                    _ => continue,
+                }
+            }
+        };
+
+        *games.entry(bot.id).or_default() += 1;
+        if opponent.builtin.is_none() {
+            *games.entry(opponent.id).or_default() += 1;
+        }
+
+        if rand::random() {
+            spawn_match(
+                state,
+                bot.clone(),
+                opponent,
+                MatchKind::Practice,
+                new_opening(state),
+            );
+        } else {
+            spawn_match(
+                state,
+                opponent,
+                bot.clone(),
+                MatchKind::Practice,
+                new_opening(state),
+            );
+        }
+    }
+
+    // The elo stored in the bots is the one they had when they logged in.
+    let elos: HashMap<_, _> = match state.storage.bots().await {
+        Ok(records) => records
+            .into_iter()
+            .map(|record| (record.id, record.elo))
+            .collect(),
+        Err(error) => {
+            error!("Could not fetch the bots' elo, using the one they logged in with: {error}");
+            HashMap::new()
+        }
+    };
+
+    let candidates = bots
+        .values()
+        .filter(|bot| bot.queue == Queue::Ladder && is_free(&games, bot))
+        .map(|bot| Candidate {
+            id: bot.id,
+            elo: elos.get(&bot.id).copied().unwrap_or(bot.elo),
+        })
+        .collect();
+
+    let now = unix_timestamp();
+    for (first, second) in pairing::pair(candidates, history, settings.rematch_cooldown, now) {
+        history.record(first, second, now);
+        let opening = history.opening(first, second, || new_opening(state));
+
+        // Each match is run async, as the better part of the time taken to run a match
+        // consists of HTTP communication and awaiting the bot's response.
+        spawn_match(
+            state,
+            bots[&first].clone(),
+            bots[&second].clone(),
+            MatchKind::Ladder,
+            opening,
+        );
+    }
+}
+
+/// Runs a match in its own task, returning a handle resolving to its outcome. Should the match
+/// panic, it is recorded as aborted rather than being left running forever.
+fn spawn_match(
+    state: &AppState,
+    bot_a: Bot,
+    bot_b: Bot,
+    kind: MatchKind,
+    opening: Opening,
+) -> JoinHandle<MatchOutcome> {
+    let state = state.clone();
+    let match_id = state.next_match_id.fetch_add(1, Ordering::Relaxed);
+
+    tokio::spawn(async move {
+        let players = [bot_a.clone(), bot_b.clone()];
+        let result = AssertUnwindSafe(launch_match(
+            state.clone(),
+            match_id,
+            bot_a,
+            bot_b,
+            kind,
+            opening,
+        ))
+        .catch_unwind()
+        .await;
+
+        match result {
+            Ok(outcome) => outcome,
+            Err(panic) => {
+                error!("Match {match_id} panicked: {}", panic_message(&*panic));
+                metrics().match_panics.inc();
+
+                // The match may have panicked before it started, or after it was recorded.
+                if state.running_matches_lock().contains_key(&match_id) {
+                    finish_match(&state, match_id, players, MatchOutcome::Aborted, kind).await;
+                }
+                MatchOutcome::Aborted
+            }
+        }
+    })
+}
+
+#[instrument(
+    name = "match",
+    skip_all,
+    fields(match_id, first = %bot_a.name, second = %bot_b.name)
+)]
+async fn launch_match(
+    state: AppState,
+    match_id: u64,
+    bot_a: Bot,
+    bot_b: Bot,
+    kind: MatchKind,
+    opening: Opening,
+) -> MatchOutcome {
     trace!(
         "Started match between {} (player 1) and {} (player 2)",
         bot_a.name.clone(),
         bot_b.name.clone()
     );
 
-    let Some(ref bot_a_socket) = bot_a.socket else {
-        return;
+    let Some(bot_a_connection) = bot_a.connection() else {
+        return MatchOutcome::Aborted;
     };
 
-    let Some(ref bot_b_socket) = bot_b.socket else {
-        return;
+    let Some(bot_b_connection) = bot_b.connection() else {
+        return MatchOutcome::Aborted;
     };
 
-    match play_match([bot_a_socket.clone(), bot_b_socket.clone()]).await {
-        Winner::Tie => handle_match_ending_tie(state, bot_a, bot_b).await,
-        Winner::ByDisqualification(bot_index, should_kick) => {
-            let bot = if bot_index == 0 { bot_a } else { bot_b };
-            handle_match_ending_disqualification(state, bot, should_kick).await
+    let players = [bot_a.clone(), bot_b.clone()];
+
+    // Register the match so that spectators can find it and follow along.
+    Span::current().record("match_id", match_id);
+    state.running_matches_lock().insert(
+        match_id,
+        Match {
+            id: match_id,
+            game: opening.game.clone(),
+            players: players.clone(),
+            started_at: unix_timestamp(),
+        },
+    );
+    let names = players.clone().map(|bot| bot.name.to_string());
+    metrics().matches_started.inc();
+    state.publish(ServerEvent::MatchStarted {
+        match_id,
+        players: names.clone(),
+    });
+    // Sending only fails if nobody is spectating, which is fine.
+    let _ = state.spectators.send(SpectatorEvent::Started {
+        match_id,
+        players: names.clone(),
+        game: opening.game.clone(),
+    });
+
+    // Bots are queried right after the previous move, so the time between two moves is the time
+    // the bot took to answer (retries included).
+    let mut last_move = Instant::now();
+    let on_move = |player, player_move, game: &Game| {
+        metrics()
+            .move_latency
+            .with_label_values(&[&*players[player as usize].name])
+            .observe(last_move.elapsed().as_secs_f64());
+        last_move = Instant::now();
+
+        if players[player as usize].builtin.is_none() {
+            state.mark_seen(players[player as usize].id);
+        }
+        if let Some(running_match) = state.running_matches_lock().get_mut(&match_id) {
+            running_match.game = game.clone();
+        }
+        let _ = state.spectators.send(match player_move {
+            Move::Sow(cell) => SpectatorEvent::Moved {
+                match_id,
+                player,
+                cell,
+                game: game.clone(),
+            },
+            Move::Swap => SpectatorEvent::Swapped {
+                match_id,
+                player,
+                game: game.clone(),
+            },
+        });
+    };
+
+    // Matches still running when the server is done waiting for them during shutdown are cut
+    // short, and recorded as aborted.
+    let winner = tokio::select! {
+        winner = play_match(
+            [bot_a_connection, bot_b_connection],
+            state.settings().matches,
+            opening,
+            on_move,
+        ) => Some(winner),
+        _ = state.reached(ShutdownPhase::Aborting) => None,
+    };
+
+    let outcome = match winner {
+        None => {
+            warn!("Aborted match {match_id} as the server is shutting down.");
+            MatchOutcome::Aborted
+        }
+        Some(Winner::Tie) => {
+            handle_match_ending_tie(state.clone(), bot_a, bot_b).await;
+            MatchOutcome::Tie
+        }
+        Some(Winner::ByDisqualification(bot_index, reason)) => {
+            metrics()
+                .disqualifications
+                .with_label_values(&[reason.name()])
+                .inc();
+
+            // The index is the one of the bot that was **not** disqualified.
+            let (winner, loser) = if bot_index == 0 {
+                (bot_a, bot_b)
+            } else {
+                (bot_b, bot_a)
+            };
+            handle_match_ending_disqualification(state.clone(), winner, loser, reason, kind).await;
+            MatchOutcome::Disqualified {
+                loser: 1 - bot_index,
+                reason: Some(reason),
+            }
         }
-        Winner::FairAndSquare(bot_index, delta) => {
+        Some(Winner::FairAndSquare(bot_index, delta)) => {
             let (winner, loser) = if bot_index == 0 {
                 (bot_a, bot_b)
             } else {
                 (bot_b, bot_a)
             };
-            handle_match_ending_fair_and_square(state, winner, loser, delta).await;
+            // Practice matches never affect the ladder.
+            if kind == MatchKind::Ladder {
+                rate_win(&state, &winner, &loser, delta).await;
+            }
+            MatchOutcome::Won {
+                winner: bot_index,
+                delta,
+            }
+        }
+    };
+
+    debug!(?outcome, "Match finished.");
+    finish_match(&state, match_id, players, outcome, kind).await;
+    outcome
+}
+
+/// Plays up to `games` matches in a row between the two logged in bots, the first one moving
+/// first in every other match. The bots play no other match whilst the series lasts (beyond the
+/// ones they were already playing), and a bot only plays one series at a time. The series stops
+/// early if either bot leaves, once the server starts shutting down, or as soon as the
+/// sequential probability ratio test (if any) concludes.
+pub async fn play_series(
+    state: &AppState,
+    names: [&str; 2],
+    games: u32,
+    kind: MatchKind,
+    sprt: Option<SprtSettings>,
+) -> SeriesSummary {
+    let mut summary = SeriesSummary::new(names.map(str::to_owned), games, sprt);
+    let mut opening = Opening::default();
+
+    let (Some(first), Some(second)) = (
+        logged_in_bot(state, names[0]).await,
+        logged_in_bot(state, names[1]).await,
+    ) else {
+        summary.stopped = Some(SeriesStop::BotLeft);
+        return summary;
+    };
+    let Some(_reservation) = SeriesReservation::new(state, [first.id, second.id]) else {
+        summary.stopped = Some(SeriesStop::Busy);
+        return summary;
+    };
+
+    for game in 0..games {
+        if state.shutdown_phase() != ShutdownPhase::Running {
+            summary.stopped = Some(SeriesStop::ShuttingDown);
+            break;
+        }
+
+        // Bots are looked up before each match, as they may have resumed their session since.
+        let (Some(first), Some(second)) = (
+            logged_in_bot(state, names[0]).await,
+            logged_in_bot(state, names[1]).await,
+        ) else {
+            summary.stopped = Some(SeriesStop::BotLeft);
+            break;
+        };
+
+        // The matches the bots were playing when the series started still count towards their
+        // limit.
+        wait_for_free_slots(state, [first.id, second.id]).await;
+
+        // Each opening is played twice, so that both bots get to play it from either seat.
+        if game % 2 == 0 {
+            opening = new_opening(state);
+        }
+        let seat = (game % 2) as u8;
+        let handle = if seat == 0 {
+            spawn_match(state, first, second, kind, opening.clone())
+        } else {
+            spawn_match(state, second, first, kind, opening.clone())
+        };
+        summary.record(seat, handle.await.unwrap_or(MatchOutcome::Aborted));
+
+        if summary.is_concluded() {
+            if game + 1 < games {
+                summary.stopped = Some(SeriesStop::SprtConcluded);
+            }
+            break;
+        }
+    }
+
+    summary
+}
+
+/// Keeps the bots of a series away from the matchmaker (and from other series) until dropped,
+/// which also happens should the series be cancelled midway.
+struct SeriesReservation {
+    state: AppState,
+    ids: [u16; 2],
+}
+
+impl SeriesReservation {
+    /// Reserves both bots, unless one of them already is.
+    fn new(state: &AppState, ids: [u16; 2]) -> Option<Self> {
+        let mut series_bots = state.series_bots_lock();
+        if ids.iter().any(|id| series_bots.contains(id)) {
+            return None;
+        }
+        series_bots.extend(ids);
+
+        Some(Self {
+            state: state.clone(),
+            ids,
+        })
+    }
+}
+
+impl Drop for SeriesReservation {
+    fn drop(&mut self) {
+        let mut series_bots = self.state.series_bots_lock();
+        for id in &self.ids {
+            series_bots.remove(id);
         }
     }
 }
 
-/// Handles what should happen when two bots play a match and one wins by a certain points delta.
-async fn handle_match_ending_fair_and_square(state: AppState, winner: Bot, loser: Bot, delta: u8) {
+/// Waits for both bots to play fewer matches than `matchmaking.max_concurrent_games`.
+async fn wait_for_free_slots(state: &AppState, ids: [u16; 2]) {
+    loop {
+        let settings = state.settings().matchmaking;
+        let busiest = {
+            let running_matches = state.running_matches_lock();
+            ids.iter()
+                .map(|id| {
+                    running_matches
+                        .values()
+                        .filter(|running| running.players.iter().any(|bot| bot.id == *id))
+                        .count()
+                })
+                .max()
+                .unwrap_or_default()
+        };
+        if busiest < settings.max_concurrent_games as usize {
+            return;
+        }
+
+        tokio::time::sleep(settings.interval).await;
+    }
+}
+
+/// Picks the starting position of a match, as configured.
+fn new_opening(state: &AppState) -> Opening {
+    let settings = state.settings().matches;
+    settings.openings.generate(settings.opening_moves)
+}
+
+/// Finds a bot that is logged in, whether or not the matchmaker already picked it up.
+async fn logged_in_bot(state: &AppState, name: &str) -> Option<Bot> {
+    let connected = state.connected_bots.lock().await;
+    if let Some(bot) = connected.iter().find(|bot| &*bot.name == name) {
+        return Some(bot.clone());
+    }
+    drop(connected);
+
+    let pending = state.pending_bots.lock().await;
+    pending.iter().find(|bot| &*bot.name == name).cloned()
+}
+
+/// Lets everyone know that a match is over and stores its result.
+async fn finish_match(
+    state: &AppState,
+    match_id: u64,
+    players: [Bot; 2],
+    outcome: MatchOutcome,
+    kind: MatchKind,
+) {
+    metrics()
+        .matches_finished
+        .with_label_values(&[outcome.to_columns().0])
+        .inc();
+    state.running_matches_lock().remove(&match_id);
+    let _ = state
+        .spectators
+        .send(SpectatorEvent::Finished { match_id, outcome });
+    state.publish(ServerEvent::MatchFinished {
+        match_id,
+        players: players.clone().map(|bot| bot.name.to_string()),
+        outcome,
+    });
+
+    match kind {
+        MatchKind::Ladder => record_match(state, players, outcome).await,
+        MatchKind::Practice => record_practice_match(state, players, outcome).await,
+    }
+}
+
+/// Fetches the bot's current elo from storage. The elo stored in the bot is the one it had when
+/// it logged in, so it gets outdated as soon as it plays a match.
+async fn current_elo(state: &AppState, bot: &Bot) -> u16 {
+    match state.storage.find_bot(&bot.name).await {
+        Ok(Some(record)) => record.elo,
+        Ok(None) => {
+            error!("Bot {} is no longer in storage.", bot.name);
+            bot.elo
+        }
+        Err(error) => {
+            error!("Error encountered when fetching player's elo: {}", error);
+            bot.elo
+        }
+    }
+}
+
+/// Stores the result of a match alongside both bots' elo once it was over.
+async fn record_match(state: &AppState, players: [Bot; 2], outcome: MatchOutcome) {
+    let elos = [
+        current_elo(state, &players[0]).await,
+        current_elo(state, &players[1]).await,
+    ];
+
+    let record = NewMatch::now([players[0].id, players[1].id], outcome, elos);
+    if let Err(error) = state.storage.record_match(record).await {
+        error!("Error encountered when recording match: {}", error);
+    }
+}
+
+/// Stores the result of a practice match, apart from the ladder's matches.
+async fn record_practice_match(state: &AppState, players: [Bot; 2], outcome: MatchOutcome) {
+    let record = NewPracticeMatch {
+        players: players
+            .clone()
+            .map(|bot| bot.builtin.is_none().then_some(bot.id)),
+        builtin: players
+            .iter()
+            .find_map(|bot| bot.builtin)
+            .map(|builtin| builtin.name().to_owned()),
+        outcome,
+        finished_at: unix_timestamp(),
+    };
+    if let Err(error) = state.storage.record_practice_match(record).await {
+        error!("Error encountered when recording practice match: {}", error);
+    }
+}
+
+/// Handles what should happen when one bot wins a match by a certain points delta, moving that
+/// much elo from the loser to the winner.
+async fn rate_win(state: &AppState, winner: &Bot, loser: &Bot, delta: u8) {
+    let _ratings = state.ratings.lock().await;
     let (winner_elo, loser_elo) = (
-        winner.elo.saturating_add(delta as u16),
-        loser.elo.saturating_sub(delta as u16),
+        current_elo(state, winner).await,
+        current_elo(state, loser).await,
     );
 
-    let connection = state.database.lock().await;
-
-    handle_database_output(connection.execute(
-        "UPDATE bots SET elo = ?1 WHERE id = ?2",
-        (winner_elo, winner.id),
-    ));
+    update_elo(
+        state,
+        winner,
+        winner_elo,
+        winner_elo.saturating_add(delta as u16),
+    )
+    .await;
+    update_elo(
+        state,
+        loser,
+        loser_elo,
+        loser_elo.saturating_sub(delta as u16),
+    )
+    .await;
+}
 
-    handle_database_output(connection.execute(
-        "UPDATE bots SET elo = ?1 WHERE id = ?2",
-        (loser_elo, loser.id),
-    ));
+/// Stores the new elo of a bot and lets everyone know about it.
+async fn update_elo(state: &AppState, bot: &Bot, old_elo: u16, new_elo: u16) {
+    if handle_storage_output(state.storage.update_elo(bot.id, new_elo).await) {
+        state.publish(ServerEvent::RatingChanged {
+            name: bot.name.to_string(),
+            old_elo,
+            new_elo,
+        });
+    }
 
-    fn handle_database_output(result: rusqlite::Result<usize>) {
+    fn handle_storage_output(result: Result<bool, StorageError>) -> bool {
         match result {
-            Ok(updated_row_count) if updated_row_count != 1 => {
-                error!(
-                    "When changing the elo of a match's winner, {} rows where updated instead of 1",
-                    updated_row_count
-                );
+            Ok(false) => {
+                error!("When changing the elo of a match's player, the player could not be found");
+                false
             }
             Err(error) => {
                 error!("Error encountered when updating player's elo: {}", error);
+                false
             }
-            _ => {}
-        };
+            Ok(true) => true,
+        }
     }
 }
 
@@ -132,25 +706,21 @@ async fn handle_match_ending_tie(_state: AppState, _bot_a: Bot, _bot_b: Bot) {
     // TODO: Figure out how to vary each player's elo in the case of a tie.
 }
 
-/// Handles what should happen when a bot loses by disqualification
+/// Handles what should happen when a bot loses by disqualification: it counts as a loss by
+/// [`DISQUALIFICATION_DELTA`] (on the ladder), and bots that could not be reached are kicked out
+/// of the server.
 async fn handle_match_ending_disqualification(
     state: AppState,
+    winner: Bot,
     disqualified_bot: Bot,
-    should_kick: bool,
+    reason: DisqualificationReason,
+    kind: MatchKind,
 ) {
-    if !should_kick {
-        return;
+    if kind == MatchKind::Ladder {
+        rate_win(&state, &winner, &disqualified_bot, DISQUALIFICATION_DELTA).await;
     }
 
-    while !state.connected_bots.lock().await.remove(&disqualified_bot) {
-        // The disqualified bot was not present in the list of connected bots. (maybe they are
-        // still on the pending bot list?)
-        warn!(
-            "Failed to kick bot named {} due to it not being found in the connected_bots set, retrying soon.",
-            disqualified_bot.name
-        );
-        tokio::time::sleep(WAIT_TIME).await;
+    if reason == DisqualificationReason::Disconnected {
+        kick(&state, &disqualified_bot, "disqualified").await;
     }
-
-    trace!("Kicked out bot {}", disqualified_bot.name);
 }
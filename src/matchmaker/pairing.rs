@@ -0,0 +1,123 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::{mancala::opening::Opening, server::storage::MatchRecord};
+
+/// What the matchmaker remembers of past matches, so that it can keep pairings varied and seats
+/// balanced.
+#[derive(Default, Debug)]
+pub(super) struct History {
+    /// Unix timestamp (in seconds) of the last match of each pair of bots, smallest id first.
+    last_played: HashMap<(u16, u16), u64>,
+    /// Matches each bot played as the first player minus the ones it played as the second.
+    seat_balance: HashMap<u16, i64>,
+    /// Opening of the last match of each pair of bots, in seat order, kept until they play it
+    /// again with seats swapped. Openings are not stored, so they are forgotten on restart.
+    openings: HashMap<(u16, u16), Opening>,
+}
+
+impl History {
+    /// Rebuilds the history from the stored matches.
+    pub(super) fn from_matches(matches: &[MatchRecord]) -> Self {
+        let mut history = Self::default();
+        for record in matches {
+            history.record(record.players[0], record.players[1], record.finished_at);
+        }
+
+        history
+    }
+
+    /// Remembers that the bots were paired at the given time, in seat order.
+    pub(super) fn record(&mut self, first: u16, second: u16, at: u64) {
+        let last_played = self.last_played.entry(key(first, second)).or_default();
+        *last_played = (*last_played).max(at);
+
+        *self.seat_balance.entry(first).or_default() += 1;
+        *self.seat_balance.entry(second).or_default() -= 1;
+    }
+
+    /// Returns the opening the bots (in seat order) should start from: the one they last played
+    /// with seats swapped if there is one, so that both get to play it from either seat, or a new
+    /// one otherwise.
+    pub(super) fn opening(
+        &mut self,
+        first: u16,
+        second: u16,
+        new_opening: impl FnOnce() -> Opening,
+    ) -> Opening {
+        if let Some(opening) = self.openings.remove(&(second, first)) {
+            return opening;
+        }
+
+        let opening = new_opening();
+        self.openings.insert((first, second), opening.clone());
+        opening
+    }
+
+    fn played_recently(&self, a: u16, b: u16, now: u64, cooldown: Duration) -> bool {
+        self.last_played
+            .get(&key(a, b))
+            .is_some_and(|&at| now.saturating_sub(at) < cooldown.as_secs())
+    }
+
+    fn seat_balance(&self, bot: u16) -> i64 {
+        self.seat_balance.get(&bot).copied().unwrap_or_default()
+    }
+}
+
+fn key(a: u16, b: u16) -> (u16, u16) {
+    (a.min(b), a.max(b))
+}
+
+/// A bot that is free to play another match, alongside its current elo.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Candidate {
+    pub id: u16,
+    pub elo: u16,
+}
+
+/// Pairs each candidate with the closest rated one it didn't play recently, starting from the
+/// lowest rated. Candidates left over are then paired the same way, rematches allowed. Each pair
+/// is returned in seat order, the bot that was in seat 0 the least taking it.
+pub(super) fn pair(
+    mut candidates: Vec<Candidate>,
+    history: &History,
+    cooldown: Duration,
+    now: u64,
+) -> Vec<(u16, u16)> {
+    candidates.sort_by_key(|candidate| (candidate.elo, candidate.id));
+
+    let mut paired = vec![false; candidates.len()];
+    let mut pairs = Vec::new();
+
+    for allow_rematches in [false, true] {
+        for i in 0..candidates.len() {
+            if paired[i] {
+                continue;
+            }
+
+            let a = candidates[i];
+            let opponent = (0..candidates.len())
+                .filter(|&j| j != i && !paired[j])
+                .filter(|&j| {
+                    allow_rematches
+                        || !history.played_recently(a.id, candidates[j].id, now, cooldown)
+                })
+                .min_by_key(|&j| candidates[j].elo.abs_diff(a.elo));
+
+            let Some(j) = opponent else {
+                continue;
+            };
+            paired[i] = true;
+            paired[j] = true;
+
+            let b = candidates[j];
+            if history.seat_balance(b.id) < history.seat_balance(a.id) {
+                pairs.push((b.id, a.id));
+            } else {
+                pairs.push((a.id, b.id));
+            }
+        }
+    }
+
+    pairs
+}
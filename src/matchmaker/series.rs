@@ -0,0 +1,215 @@
+use serde::Serialize;
+
+use crate::server::storage::{MatchOutcome, MatchResult};
+
+/// Results of a series of matches between two bots, from the point of view of the first one.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SeriesSummary {
+    pub bots: [String; 2],
+    /// Amount of matches that were asked for.
+    pub requested: u32,
+    /// Amount of matches actually played, aborted ones included.
+    pub played: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub ties: u32,
+    pub aborted: u32,
+    /// Sum of the score differences of the matches won on the board, positive when the first
+    /// bot scored more.
+    pub score_difference: i64,
+    /// Average score of the first bot (a win being worth 1 and a tie 0.5), aborted matches
+    /// aside. Null until a match is played to the end.
+    pub score: Option<Interval>,
+    /// Elo difference between the first and second bot implied by the score. Bounds are null
+    /// when they are infinite, such as when a bot won every match.
+    pub elo_difference: Option<Interval>,
+    /// Sequential probability ratio test, if one was asked for.
+    pub sprt: Option<Sprt>,
+    /// Every match of the series, in the order they were played.
+    pub matches: Vec<SeriesMatch>,
+    /// Why the series ended before every match was played, if it did.
+    pub stopped: Option<SeriesStop>,
+}
+
+/// A match of a series.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct SeriesMatch {
+    /// Seat of the first bot of the series in this match.
+    pub seat: u8,
+    pub result: MatchResult,
+    pub outcome: MatchOutcome,
+}
+
+/// Why a series ended early.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeriesStop {
+    /// One of the bots is no longer logged in.
+    BotLeft,
+    /// One of the bots is already playing another series.
+    Busy,
+    /// The server is shutting down.
+    ShuttingDown,
+    /// The sequential probability ratio test reached a verdict.
+    SprtConcluded,
+}
+
+/// An estimate alongside its 95% confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Interval {
+    pub estimate: Option<f64>,
+    pub lower: Option<f64>,
+    pub upper: Option<f64>,
+}
+
+/// Hypotheses tested by a sequential probability ratio test: the first bot is `elo0` stronger
+/// than the second one (H0), or `elo1` stronger (H1). Errors happen with a probability of at
+/// most `alpha` (accepting H1 when H0 is true) and `beta` (the other way around).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct SprtSettings {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+/// State of a sequential probability ratio test, which ends once the log-likelihood ratio leaves
+/// its bounds.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Sprt {
+    #[serde(flatten)]
+    pub settings: SprtSettings,
+    pub llr: f64,
+    pub lower_bound: f64,
+    pub upper_bound: f64,
+    /// Null whilst the test is inconclusive.
+    pub verdict: Option<SprtVerdict>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SprtVerdict {
+    /// The first bot is not `elo1` stronger than the second one.
+    H0,
+    /// The first bot is at least `elo1` stronger than the second one.
+    H1,
+}
+
+/// Quantile of the normal distribution used for 95% confidence intervals.
+const Z_95: f64 = 1.959_963_984_540_054;
+
+/// Wins and losses added to the results the sequential probability ratio test is run on, so that
+/// series whose matches all ended the same way (and thus show no variance) still move it.
+const SPRT_PSEUDO_RESULTS: f64 = 0.5;
+
+impl SeriesSummary {
+    pub fn new(bots: [String; 2], requested: u32, sprt: Option<SprtSettings>) -> Self {
+        Self {
+            bots,
+            requested,
+            sprt: sprt.map(|settings| Sprt {
+                settings,
+                llr: 0.0,
+                lower_bound: (settings.beta / (1.0 - settings.alpha)).ln(),
+                upper_bound: ((1.0 - settings.beta) / settings.alpha).ln(),
+                verdict: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the series can stop, as the sequential probability ratio test reached a verdict.
+    pub fn is_concluded(&self) -> bool {
+        self.sprt.is_some_and(|sprt| sprt.verdict.is_some())
+    }
+
+    /// Adds a match in which the first bot of the series sat at the given seat.
+    pub fn record(&mut self, seat: u8, outcome: MatchOutcome) {
+        let result = outcome.result_for(seat);
+        match result {
+            MatchResult::Win => self.wins += 1,
+            MatchResult::Loss => self.losses += 1,
+            MatchResult::Tie => self.ties += 1,
+            MatchResult::Aborted => self.aborted += 1,
+        }
+        if let MatchOutcome::Won { delta, .. } = outcome {
+            self.score_difference += match result {
+                MatchResult::Win => delta as i64,
+                _ => -(delta as i64),
+            };
+        }
+
+        self.played += 1;
+        self.matches.push(SeriesMatch {
+            seat,
+            result,
+            outcome,
+        });
+        self.update_statistics();
+    }
+
+    fn update_statistics(&mut self) {
+        let games = (self.wins + self.losses + self.ties) as f64;
+        if games == 0.0 {
+            return;
+        }
+
+        let (wins, ties, losses) = (
+            self.wins as f64 / games,
+            self.ties as f64 / games,
+            self.losses as f64 / games,
+        );
+        let mean = wins + ties / 2.0;
+        let variance =
+            wins * (1.0 - mean).powi(2) + ties * (0.5 - mean).powi(2) + losses * mean.powi(2);
+        let margin = Z_95 * (variance / games).sqrt();
+
+        let (lower, upper) = ((mean - margin).max(0.0), (mean + margin).min(1.0));
+        self.score = Some(Interval {
+            estimate: Some(mean),
+            lower: Some(lower),
+            upper: Some(upper),
+        });
+        self.elo_difference = Some(Interval {
+            estimate: elo_from_score(mean),
+            lower: elo_from_score(lower),
+            upper: elo_from_score(upper),
+        });
+
+        if let Some(sprt) = &mut self.sprt {
+            // Normal approximation of the generalized SPRT, as used by engine testing frameworks.
+            let (wins, ties, losses) = (
+                self.wins as f64 + SPRT_PSEUDO_RESULTS,
+                self.ties as f64,
+                self.losses as f64 + SPRT_PSEUDO_RESULTS,
+            );
+            let games = wins + ties + losses;
+            let mean = (wins + ties / 2.0) / games;
+            let variance =
+                (wins * (1.0 - mean).powi(2) + ties * (0.5 - mean).powi(2) + losses * mean.powi(2))
+                    / games;
+
+            let s0 = score_from_elo(sprt.settings.elo0);
+            let s1 = score_from_elo(sprt.settings.elo1);
+            sprt.llr = games * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance);
+
+            sprt.verdict = if sprt.llr >= sprt.upper_bound {
+                Some(SprtVerdict::H1)
+            } else if sprt.llr <= sprt.lower_bound {
+                Some(SprtVerdict::H0)
+            } else {
+                None
+            };
+        }
+    }
+}
+
+/// Expected score of a bot that is the given elo stronger than its opponent.
+fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Inverse of [`score_from_elo`], None when the difference is infinite.
+fn elo_from_score(score: f64) -> Option<f64> {
+    (score > 0.0 && score < 1.0).then(|| -400.0 * (1.0 / score - 1.0).log10())
+}
@@ -0,0 +1,344 @@
+#![cfg(test)]
+
+use std::sync::Arc;
+
+use super::{
+    series::{SprtSettings, SprtVerdict},
+    *,
+};
+use crate::server::storage::{memory::MemoryStorage, MatchRecord, MatchResult, Storage};
+
+#[test]
+fn restart_delay_doubles_up_to_the_maximum() {
+    assert_eq!(restart_delay(1), MIN_RESTART_DELAY);
+    assert_eq!(restart_delay(2), MIN_RESTART_DELAY * 2);
+    assert_eq!(restart_delay(4), MIN_RESTART_DELAY * 8);
+    assert_eq!(restart_delay(7), MAX_RESTART_DELAY);
+    assert_eq!(restart_delay(u32::MAX), MAX_RESTART_DELAY);
+}
+
+#[test]
+fn panic_messages_are_extracted() {
+    let panic = std::panic::catch_unwind(|| panic!("static message")).unwrap_err();
+    assert_eq!(panic_message(&*panic), "static message");
+
+    let panic = std::panic::catch_unwind(|| panic!("formatted {}", 42)).unwrap_err();
+    assert_eq!(panic_message(&*panic), "formatted 42");
+
+    let panic = std::panic::catch_unwind(|| std::panic::panic_any(42)).unwrap_err();
+    assert_eq!(panic_message(&*panic), "unknown panic");
+}
+
+fn candidates(elos: &[(u16, u16)]) -> Vec<Candidate> {
+    elos.iter()
+        .map(|&(id, elo)| Candidate { id, elo })
+        .collect()
+}
+
+const COOLDOWN: Duration = Duration::from_secs(30);
+
+#[test]
+fn bots_of_similar_rating_are_paired() {
+    let pairs = pairing::pair(
+        candidates(&[(1, 1000), (2, 1400), (3, 1010), (4, 1390), (5, 1200)]),
+        &History::default(),
+        COOLDOWN,
+        100,
+    );
+
+    assert_eq!(pairs, [(1, 3), (5, 4)]);
+}
+
+#[test]
+fn recent_opponents_are_avoided_unless_nobody_else_is_left() {
+    let mut history = History::default();
+    history.record(1, 2, 100);
+    let bots = candidates(&[(1, 1000), (2, 1000), (3, 1100), (4, 1100)]);
+
+    // Bot 1 was in seat 0 in its last match, and bot 2 in seat 1, so they swap seats.
+    let pairs = pairing::pair(bots.clone(), &history, COOLDOWN, 110);
+    assert_eq!(pairs, [(3, 1), (2, 4)]);
+
+    let pairs = pairing::pair(bots[..2].to_vec(), &history, COOLDOWN, 110);
+    assert_eq!(pairs, [(2, 1)]);
+
+    // Once the cooldown is over, the closest rated opponent is picked again.
+    let pairs = pairing::pair(bots, &history, COOLDOWN, 200);
+    assert_eq!(pairs, [(2, 1), (3, 4)]);
+}
+
+#[test]
+fn seats_are_balanced() {
+    let matches: Vec<_> = [[1, 2], [1, 3], [2, 3]]
+        .into_iter()
+        .enumerate()
+        .map(|(id, players)| MatchRecord {
+            id: id as u64,
+            players,
+            outcome: MatchOutcome::Tie,
+            elos: [1000, 1000],
+            finished_at: 0,
+        })
+        .collect();
+    let history = History::from_matches(&matches);
+
+    // Bot 1 was in seat 0 twice, and bot 3 never was.
+    let pairs = pairing::pair(candidates(&[(1, 1000), (3, 1000)]), &history, COOLDOWN, 100);
+    assert_eq!(pairs, [(3, 1)]);
+}
+
+#[test]
+fn series_summary_takes_the_first_bot_point_of_view() {
+    let mut summary = SeriesSummary::new(["a".to_owned(), "b".to_owned()], 4, None);
+    summary.record(
+        0,
+        MatchOutcome::Won {
+            winner: 0,
+            delta: 6,
+        },
+    );
+    summary.record(
+        1,
+        MatchOutcome::Won {
+            winner: 0,
+            delta: 2,
+        },
+    );
+    summary.record(
+        1,
+        MatchOutcome::Disqualified {
+            loser: 0,
+            reason: None,
+        },
+    );
+    summary.record(0, MatchOutcome::Aborted);
+
+    assert_eq!(
+        [summary.wins, summary.losses, summary.ties, summary.aborted],
+        [2, 1, 0, 1]
+    );
+    assert_eq!(summary.played, 4);
+    assert_eq!(summary.score_difference, 4);
+    assert_eq!(summary.matches[1].result, MatchResult::Loss);
+}
+
+#[test]
+fn series_statistics() {
+    let sprt = SprtSettings {
+        elo0: 0.0,
+        elo1: 10.0,
+        alpha: 0.05,
+        beta: 0.05,
+    };
+    let mut summary = SeriesSummary::new(["a".to_owned(), "b".to_owned()], 1000, Some(sprt));
+    let win = MatchOutcome::Won {
+        winner: 0,
+        delta: 1,
+    };
+    let loss = MatchOutcome::Won {
+        winner: 1,
+        delta: 1,
+    };
+
+    // 60 wins, 10 ties and 30 losses.
+    let record = |summary: &mut SeriesSummary| {
+        for _ in 0..6 {
+            summary.record(0, win);
+        }
+        summary.record(0, MatchOutcome::Tie);
+        for _ in 0..3 {
+            summary.record(0, loss);
+        }
+    };
+    for _ in 0..10 {
+        record(&mut summary);
+    }
+
+    let score = summary.score.unwrap();
+    assert!((score.estimate.unwrap() - 0.65).abs() < 1e-9);
+    assert!((score.upper.unwrap() - score.estimate.unwrap() - 0.0882).abs() < 1e-3);
+    let elo = summary.elo_difference.unwrap();
+    assert!((elo.estimate.unwrap() - 107.5).abs() < 0.1);
+    assert!(elo.lower.unwrap() < elo.estimate.unwrap() && elo.estimate < elo.upper);
+
+    // The test can't tell yet, but would after three times as many matches.
+    let sprt = summary.sprt.unwrap();
+    assert!((sprt.llr - 1.0106).abs() < 1e-3);
+    assert!(!summary.is_concluded());
+    for _ in 0..20 {
+        record(&mut summary);
+    }
+    assert_eq!(summary.sprt.unwrap().verdict, Some(SprtVerdict::H1));
+}
+
+#[test]
+fn series_that_always_end_the_same_way_conclude() {
+    let sprt = SprtSettings {
+        elo0: 0.0,
+        elo1: 10.0,
+        alpha: 0.05,
+        beta: 0.05,
+    };
+    let win = MatchOutcome::Won {
+        winner: 0,
+        delta: 1,
+    };
+
+    let mut summary = SeriesSummary::new(["a".to_owned(), "b".to_owned()], 1000, Some(sprt));
+    summary.record(0, win);
+    assert!(summary.sprt.unwrap().llr > 0.0);
+    while !summary.is_concluded() {
+        summary.record(0, win);
+    }
+    assert_eq!(summary.sprt.unwrap().verdict, Some(SprtVerdict::H1));
+    assert_eq!(summary.wins, summary.played);
+
+    // Ties only show that neither bot is much stronger, which takes many more matches to tell.
+    let mut summary = SeriesSummary::new(["a".to_owned(), "b".to_owned()], 1000, Some(sprt));
+    summary.record(0, MatchOutcome::Tie);
+    assert!(summary.sprt.unwrap().llr < 0.0);
+    while !summary.is_concluded() {
+        summary.record(0, MatchOutcome::Tie);
+    }
+    assert_eq!(summary.sprt.unwrap().verdict, Some(SprtVerdict::H0));
+}
+
+#[test]
+fn elo_is_unbounded_when_every_match_is_won() {
+    let mut summary = SeriesSummary::new(["a".to_owned(), "b".to_owned()], 2, None);
+    summary.record(
+        1,
+        MatchOutcome::Disqualified {
+            loser: 0,
+            reason: None,
+        },
+    );
+
+    let elo = summary.elo_difference.unwrap();
+    assert_eq!((elo.estimate, elo.upper), (None, None));
+}
+
+/// Stores a ladder bot with the given elo, returning it as if it had logged in.
+async fn stored_bot(state: &AppState, name: &str, elo: u16) -> Bot {
+    assert!(state.storage.insert_bot(name, "", elo).await.unwrap());
+    let record = state.storage.find_bot(name).await.unwrap().unwrap();
+
+    Bot {
+        name: record.name.into(),
+        id: record.id,
+        elo: record.elo,
+        socket: None,
+        secret: Arc::new([]),
+        reconnections: Arc::new(tokio::sync::watch::channel(0).0),
+        queue: Queue::Ladder,
+        builtin: None,
+    }
+}
+
+#[tokio::test]
+async fn disqualifications_move_a_fixed_amount_of_elo_on_the_ladder() {
+    let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::default());
+    let state = AppState::new(storage, Default::default());
+    let winner = stored_bot(&state, "winner", 1000).await;
+    let loser = stored_bot(&state, "loser", 1000).await;
+
+    handle_match_ending_disqualification(
+        state.clone(),
+        winner.clone(),
+        loser.clone(),
+        DisqualificationReason::IllegalMove,
+        MatchKind::Ladder,
+    )
+    .await;
+    assert_eq!(
+        current_elo(&state, &winner).await,
+        1000 + DISQUALIFICATION_DELTA as u16
+    );
+    assert_eq!(
+        current_elo(&state, &loser).await,
+        1000 - DISQUALIFICATION_DELTA as u16
+    );
+
+    // Practice matches are unrated, however they end.
+    handle_match_ending_disqualification(
+        state.clone(),
+        winner.clone(),
+        loser.clone(),
+        DisqualificationReason::TimedOut,
+        MatchKind::Practice,
+    )
+    .await;
+    assert_eq!(
+        current_elo(&state, &winner).await,
+        1000 + DISQUALIFICATION_DELTA as u16
+    );
+}
+
+#[tokio::test]
+async fn bots_only_practice_against_each_other_when_both_asked_to() {
+    let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::default());
+    let state = AppState::new(storage, Default::default());
+    let practicing = |mut bot: Bot, opponent: &str| {
+        bot.queue = Queue::Practice(PracticeOpponent::Bot(opponent.into()));
+        bot
+    };
+
+    let bots = [
+        stored_bot(&state, "ladder", 1000).await,
+        practicing(stored_bot(&state, "intruder", 1000).await, "ladder"),
+        practicing(stored_bot(&state, "first", 1000).await, "second"),
+        practicing(stored_bot(&state, "second", 1000).await, "first"),
+    ];
+    state.pending_bots.lock().await.extend(bots);
+
+    // The bots have no socket, so the matches are aborted right away, but counted nonetheless.
+    run_round(&state, &mut History::default()).await;
+    assert_eq!(state.next_match_id.load(Ordering::Relaxed), 2);
+}
+
+#[tokio::test]
+async fn bots_playing_a_series_are_left_alone() {
+    let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::default());
+    let state = AppState::new(storage, Default::default());
+    let bots = [
+        stored_bot(&state, "a", 1000).await,
+        stored_bot(&state, "b", 1000).await,
+    ];
+    state.pending_bots.lock().await.extend(bots.clone());
+
+    let reservation = SeriesReservation::new(&state, bots.clone().map(|bot| bot.id)).unwrap();
+    run_round(&state, &mut History::default()).await;
+    assert_eq!(state.next_match_id.load(Ordering::Relaxed), 1);
+
+    // A bot plays one series at a time.
+    let summary = play_series(&state, ["b", "a"], 2, MatchKind::Practice, None).await;
+    assert_eq!(summary.stopped, Some(SeriesStop::Busy));
+    assert_eq!(summary.played, 0);
+
+    drop(reservation);
+    run_round(&state, &mut History::default()).await;
+    assert_eq!(state.next_match_id.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn ladder_openings_are_replayed_with_seats_swapped() {
+    let mut history = History::default();
+    let mut generated = 0;
+    let mut new_opening = || {
+        generated += 1;
+        Opening {
+            player: generated,
+            ..Default::default()
+        }
+    };
+
+    assert_eq!(history.opening(1, 2, &mut new_opening).player, 1);
+    // Other pairs and the same seats get their own opening.
+    assert_eq!(history.opening(1, 3, &mut new_opening).player, 2);
+    assert_eq!(history.opening(1, 2, &mut new_opening).player, 3);
+
+    assert_eq!(history.opening(2, 1, &mut new_opening).player, 3);
+    assert_eq!(history.opening(3, 1, &mut new_opening).player, 2);
+    // Each opening is only replayed once.
+    assert_eq!(history.opening(2, 1, &mut new_opening).player, 4);
+}
@@ -2,15 +2,35 @@ use crate::server::app_state::AppState;
 
 use axum::{routing::get, Router};
 
+mod challenge;
 mod display;
+mod events;
+mod info;
+mod leaderboard;
 mod login;
+mod practice;
+mod presence;
+mod profile;
 mod register;
+mod resume;
+mod spectate;
+mod tests;
 
 /// Function that creates the router for the server's api.
 pub fn routes(state: AppState) -> Router {
     Router::new()
         .route("/register", get(register::register_bot))
         .route("/login", get(login::login))
+        .route("/resume", get(resume::resume))
         .route("/display", get(display::show_bots))
+        .route("/leaderboard", get(leaderboard::leaderboard))
+        .route("/bots/{name}", get(profile::bot_profile))
+        .route("/bots/{name}/practice", get(practice::practice_matches))
+        .route("/matches", get(spectate::running_matches))
+        .route("/spectate", get(spectate::spectate))
+        .route("/events", get(events::events))
+        .route("/presence", get(presence::presence))
+        .route("/info", get(info::info))
+        .route("/challenge", get(challenge::challenge))
         .with_state(state)
 }
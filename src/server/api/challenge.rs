@@ -0,0 +1,180 @@
+use axum::{
+    debug_handler,
+    extract::{Query, State},
+    response::IntoResponse,
+    Json,
+};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    matchmaker::{
+        play_series,
+        series::{SeriesStop, SeriesSummary, SprtSettings},
+        MatchKind,
+    },
+    server::{app_state::AppState, shutdown::ShutdownPhase, storage::StorageError},
+};
+
+use super::login::{authenticate, LoginBotError};
+
+/// Most matches a single challenge can ask for.
+const MAX_GAMES: u32 = 1000;
+
+/// Error rates of the sequential probability ratio test, unless given.
+const DEFAULT_SPRT_ERROR_RATE: f64 = 0.05;
+
+/// Plays a series of matches between the authenticated bot and the given opponent, both of which
+/// must be logged in, and answers with its summary once it is over. Seats alternate from one
+/// match to the next, and the series is unrated (and stored with the practice matches) unless
+/// asked otherwise, which takes the opponent's password as its consent. Giving `sprt_elo0` and
+/// `sprt_elo1` runs a sequential probability ratio test alongside the series, which ends it as
+/// soon as it concludes.
+#[debug_handler]
+pub(super) async fn challenge(
+    State(state): State<AppState>,
+    Query(payload): Query<ChallengePayload>,
+) -> Result<Json<SeriesSummary>, ChallengeError> {
+    if state.shutdown_phase() != ShutdownPhase::Running {
+        return Err(ChallengeError::ShuttingDown);
+    }
+    if !(1..=MAX_GAMES).contains(&payload.games) {
+        return Err(ChallengeError::InvalidGames);
+    }
+    if payload.name == payload.opponent {
+        return Err(ChallengeError::SameBot);
+    }
+
+    let sprt = match (payload.sprt_elo0, payload.sprt_elo1) {
+        (None, None) => None,
+        (Some(elo0), Some(elo1)) if elo0 < elo1 => Some(SprtSettings {
+            elo0,
+            elo1,
+            alpha: payload.sprt_alpha.unwrap_or(DEFAULT_SPRT_ERROR_RATE),
+            beta: payload.sprt_beta.unwrap_or(DEFAULT_SPRT_ERROR_RATE),
+        }),
+        _ => return Err(ChallengeError::InvalidSprt),
+    };
+    let is_rate = |rate: f64| rate > 0.0 && rate < 1.0;
+    if sprt.is_some_and(|sprt| !is_rate(sprt.alpha) || !is_rate(sprt.beta)) {
+        return Err(ChallengeError::InvalidSprt);
+    }
+
+    authenticate(&state, &payload.name, payload.password).await?;
+    if state.storage.find_bot(&payload.opponent).await?.is_none() {
+        return Err(ChallengeError::UnknownOpponent);
+    }
+
+    // Rated series move elo, so the opponent must agree to them lest bots farm weaker ones.
+    if payload.rated {
+        let opponent_password = payload
+            .opponent_password
+            .ok_or(ChallengeError::MissingConsent)?;
+        authenticate(&state, &payload.opponent, opponent_password).await?;
+    }
+
+    let kind = if payload.rated {
+        MatchKind::Ladder
+    } else {
+        MatchKind::Practice
+    };
+    let names = [payload.name.as_str(), payload.opponent.as_str()];
+    let summary = play_series(&state, names, payload.games, kind, sprt).await;
+
+    // Nothing was played if either bot wasn't there (or free) to begin with.
+    if summary.played == 0 {
+        match summary.stopped {
+            Some(SeriesStop::BotLeft) => return Err(ChallengeError::NotLoggedIn),
+            Some(SeriesStop::Busy) => return Err(ChallengeError::Busy),
+            _ => {}
+        }
+    }
+
+    Ok(Json(summary))
+}
+
+#[derive(Deserialize)]
+pub(super) struct ChallengePayload {
+    name: String,
+    password: String,
+    opponent: String,
+    #[serde(default = "default_games")]
+    games: u32,
+    /// Whether the matches count towards the ladder, which requires `opponent_password`.
+    #[serde(default)]
+    rated: bool,
+    opponent_password: Option<String>,
+    /// Elo difference (from the challenger's point of view) of the null hypothesis of the
+    /// sequential probability ratio test.
+    sprt_elo0: Option<f64>,
+    /// Elo difference of the alternative hypothesis, which must be greater than `sprt_elo0`.
+    sprt_elo1: Option<f64>,
+    sprt_alpha: Option<f64>,
+    sprt_beta: Option<f64>,
+}
+
+fn default_games() -> u32 {
+    2
+}
+
+#[derive(Error, Debug)]
+pub(super) enum ChallengeError {
+    #[error("invalid credentials: {0}")]
+    Credentials(#[from] LoginBotError),
+
+    #[error("storage error: {0}")]
+    StorageError(#[from] StorageError),
+
+    #[error("invalid amount of games")]
+    InvalidGames,
+
+    #[error("invalid sequential probability ratio test settings")]
+    InvalidSprt,
+
+    #[error("a bot can't challenge itself")]
+    SameBot,
+
+    #[error("opponent is not in the database")]
+    UnknownOpponent,
+
+    #[error("rated series require the opponent's password")]
+    MissingConsent,
+
+    #[error("both bots must be logged in")]
+    NotLoggedIn,
+
+    #[error("one of the bots is already playing a series")]
+    Busy,
+
+    #[error("the server is shutting down")]
+    ShuttingDown,
+}
+
+impl IntoResponse for ChallengeError {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            Self::Credentials(error) => return error.into_response(),
+            Self::StorageError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ""),
+            Self::InvalidGames => (StatusCode::BAD_REQUEST, "games must be between 1 and 1000"),
+            Self::InvalidSprt => (
+                StatusCode::BAD_REQUEST,
+                "sprt_elo0 and sprt_elo1 must both be given with sprt_elo0 < sprt_elo1, and \
+                 sprt_alpha and sprt_beta must be between 0 and 1",
+            ),
+            Self::SameBot => (StatusCode::BAD_REQUEST, "a bot can't challenge itself"),
+            Self::UnknownOpponent => (StatusCode::NOT_FOUND, "unknown opponent"),
+            Self::MissingConsent => (
+                StatusCode::FORBIDDEN,
+                "rated series require the opponent's password",
+            ),
+            Self::NotLoggedIn => (StatusCode::CONFLICT, "both bots must be logged in"),
+            Self::Busy => (
+                StatusCode::CONFLICT,
+                "one of the bots is already playing a series",
+            ),
+            Self::ShuttingDown => (StatusCode::SERVICE_UNAVAILABLE, "server is shutting down"),
+        }
+        .into_response()
+    }
+}
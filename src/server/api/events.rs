@@ -0,0 +1,29 @@
+use std::convert::Infallible;
+
+use axum::{
+    debug_handler,
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+use crate::server::app_state::AppState;
+
+/// Streams lobby and ladder events (bots connecting, matches starting, ratings changing...) as
+/// server-sent events, for clients that would rather not use web sockets.
+#[debug_handler]
+pub(super) async fn events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = BroadcastStream::new(state.events.subscribe()).filter_map(|event| {
+        // Subscribers that fall behind simply miss the events they lagged on.
+        let event = event.ok()?;
+        let sse_event = Event::default()
+            .event(event.name())
+            .json_data(&event)
+            .ok()?;
+        Some(Ok(sse_event))
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,85 @@
+use axum::{debug_handler, extract::State, Json};
+use serde::Serialize;
+
+use crate::{
+    mancala::{
+        builtin::BuiltinBot, opening::Openings, play_match::PROTOCOL_VERSION, INITIAL_SEEDS, PITS,
+    },
+    server::app_state::{AppState, BUILTIN_PREFIX},
+};
+
+/// Describes the server, so that bots can check they are compatible before connecting.
+#[debug_handler]
+pub(super) async fn info(State(state): State<AppState>) -> Json<ServerInfo> {
+    let settings = state.settings().matches;
+
+    Json(ServerInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        protocol_version: PROTOCOL_VERSION,
+        rules: Rules {
+            pits_per_side: PITS,
+            initial_seeds: INITIAL_SEEDS,
+            pie_rule: settings.pie_rule,
+            openings: settings.openings,
+            opening_moves: (settings.openings == Openings::RandomMoves)
+                .then_some(settings.opening_moves),
+        },
+        time_controls: TimeControls {
+            move_timeout_ms: settings.move_timeout.as_millis() as u64,
+            reconnection_grace_ms: settings.reconnection_grace.as_millis() as u64,
+            query_retries: settings.query_retries,
+            connection_retries: settings.connection_retries,
+        },
+        builtin_bots: BuiltinBot::ALL
+            .iter()
+            .map(|bot| format!("{BUILTIN_PREFIX}{}", bot.name()))
+            .collect(),
+        load: Load {
+            pending_bots: state.pending_bots.lock().await.len(),
+            connected_bots: state.connected_bots.lock().await.len(),
+            running_matches: state.running_matches_lock().len(),
+        },
+    })
+}
+
+#[derive(Serialize)]
+pub(super) struct ServerInfo {
+    version: &'static str,
+    protocol_version: u32,
+    rules: Rules,
+    time_controls: TimeControls,
+    /// Opponents that can be chosen for practice at login.
+    builtin_bots: Vec<String>,
+    load: Load,
+}
+
+#[derive(Serialize)]
+struct Rules {
+    pits_per_side: usize,
+    initial_seeds: u8,
+    /// Whether the player moving second may swap sides after the first move.
+    pie_rule: bool,
+    /// How the starting position of each match is picked.
+    openings: Openings,
+    /// Amount of random moves each opening starts with, when openings are random moves.
+    opening_moves: Option<u8>,
+}
+
+#[derive(Serialize)]
+struct TimeControls {
+    /// How long a bot has to answer a query.
+    move_timeout_ms: u64,
+    /// How long a bot whose connection dropped mid-match has to resume its session.
+    reconnection_grace_ms: u64,
+    /// How many invalid answers a bot can give before being disqualified.
+    query_retries: u8,
+    /// How many times a bot can fail to be reached before being disqualified.
+    connection_retries: u8,
+}
+
+#[derive(Serialize)]
+struct Load {
+    pending_bots: usize,
+    connected_bots: usize,
+    running_matches: usize,
+}
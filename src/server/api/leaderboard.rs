@@ -0,0 +1,182 @@
+use std::collections::HashSet;
+
+use axum::{
+    debug_handler,
+    extract::{Query, State},
+    response::IntoResponse,
+    Json,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::server::{
+    app_state::AppState,
+    storage::{BotStats, StorageError},
+};
+
+/// Amount of bots per page when the client doesn't specify it.
+const DEFAULT_PER_PAGE: usize = 50;
+
+/// Upper bound of the amount of bots per page, to avoid dumping the whole ladder at once.
+const MAX_PER_PAGE: usize = 200;
+
+#[debug_handler]
+pub(super) async fn leaderboard(
+    State(state): State<AppState>,
+    Query(query): Query<LeaderboardQuery>,
+) -> Result<Json<LeaderboardPayload>, LeaderboardError> {
+    if query.page == 0 || !(1..=MAX_PER_PAGE).contains(&query.per_page) {
+        return Err(LeaderboardError::InvalidPage);
+    }
+
+    let online = online_bots(&state).await;
+    let mut stats = state.storage.bot_stats().await?;
+
+    // Bots come sorted by elo, so their rank is simply their position.
+    let mut entries: Vec<_> = state
+        .storage
+        .bots()
+        .await?
+        .into_iter()
+        .enumerate()
+        .map(|(index, bot)| LeaderboardEntry {
+            rank: index + 1,
+            online: online.contains(&bot.id),
+            stats: stats.remove(&bot.id).unwrap_or_default(),
+            name: bot.name,
+            elo: bot.elo,
+        })
+        .filter(|entry| query.online.is_none_or(|online| entry.online == online))
+        .collect();
+
+    // Names are naturally read in alphabetical order, whereas for everything else the highest
+    // values are the most interesting.
+    let order = query.order.unwrap_or(match query.sort {
+        SortKey::Name => Order::Ascending,
+        _ => Order::Descending,
+    });
+
+    // The sort is stable, so bots that compare equal stay in rank order.
+    entries.sort_by(|a, b| {
+        let ordering = match query.sort {
+            SortKey::Elo => a.elo.cmp(&b.elo),
+            SortKey::Name => a.name.cmp(&b.name),
+            SortKey::Games => a.stats.games.cmp(&b.stats.games),
+            SortKey::Wins => a.stats.wins.cmp(&b.stats.wins),
+        };
+        match order {
+            Order::Ascending => ordering,
+            Order::Descending => ordering.reverse(),
+        }
+    });
+
+    let total = entries.len();
+    let bots = entries
+        .into_iter()
+        .skip((query.page - 1) * query.per_page)
+        .take(query.per_page)
+        .collect();
+
+    Ok(Json(LeaderboardPayload {
+        page: query.page,
+        per_page: query.per_page,
+        total,
+        bots,
+    }))
+}
+
+/// Ids of every bot currently logged in, whether it already joined the ladder or not.
+pub(super) async fn online_bots(state: &AppState) -> HashSet<u16> {
+    let mut online: HashSet<_> = state
+        .connected_bots
+        .lock()
+        .await
+        .iter()
+        .map(|bot| bot.id)
+        .collect();
+    online.extend(state.pending_bots.lock().await.iter().map(|bot| bot.id));
+    online
+}
+
+#[derive(Deserialize)]
+pub(super) struct LeaderboardQuery {
+    /// Index of the page, starting at 1.
+    #[serde(default = "first_page")]
+    page: usize,
+
+    #[serde(default = "default_per_page")]
+    per_page: usize,
+
+    #[serde(default)]
+    sort: SortKey,
+
+    order: Option<Order>,
+
+    /// Only keep bots that are (or aren't) logged in.
+    online: Option<bool>,
+}
+
+fn first_page() -> usize {
+    1
+}
+
+fn default_per_page() -> usize {
+    DEFAULT_PER_PAGE
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum SortKey {
+    #[default]
+    Elo,
+    Name,
+    Games,
+    Wins,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Order {
+    Ascending,
+    Descending,
+}
+
+#[derive(Serialize)]
+pub(super) struct LeaderboardPayload {
+    page: usize,
+    per_page: usize,
+    /// Amount of bots matching the filters, across all pages.
+    total: usize,
+    bots: Vec<LeaderboardEntry>,
+}
+
+#[derive(Serialize)]
+struct LeaderboardEntry {
+    /// Position of the bot in the ladder (by elo), regardless of filters and sorting.
+    rank: usize,
+    name: String,
+    elo: u16,
+    online: bool,
+    #[serde(flatten)]
+    stats: BotStats,
+}
+
+#[derive(Error, Debug)]
+pub(super) enum LeaderboardError {
+    #[error("page must be at least 1 and per_page between 1 and {MAX_PER_PAGE}")]
+    InvalidPage,
+
+    #[error("error whilst querying storage: {0}")]
+    StorageError(#[from] StorageError),
+}
+
+impl IntoResponse for LeaderboardError {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            Self::InvalidPage => (StatusCode::BAD_REQUEST, "invalid page"),
+            Self::StorageError(_error) => (StatusCode::INTERNAL_SERVER_ERROR, ""),
+        }
+        .into_response()
+    }
+}
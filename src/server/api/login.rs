@@ -1,26 +1,35 @@
 use std::sync::Arc;
 
-use crate::server::app_state::{AppState, Bot};
+use crate::{
+    mancala::builtin::BuiltinBot,
+    server::{
+        app_state::{AppState, Bot, PracticeOpponent, Queue, BUILTIN_PREFIX},
+        bot_socket::BotSocket,
+        events::ServerEvent,
+        shutdown::ShutdownPhase,
+        storage::{BotRecord, StorageError},
+    },
+};
 
 use argon2::{Argon2, PasswordHash, PasswordVerifier};
 use axum::{
     debug_handler,
     extract::{Query, State, WebSocketUpgrade},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 use rand::{distr::StandardUniform, rngs::StdRng, Rng, SeedableRng};
 use reqwest::StatusCode;
-use rusqlite::{params, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tokio::sync::Mutex;
-use tracing::error;
+use tokio::sync::watch;
 
-type Token = String;
+/// Header of the upgrade response in which the bot's session token is sent.
+pub const TOKEN_HEADER: &str = "x-session-token";
 
+/// Claims of a bot's session token, signed with the bot's per-session secret.
 #[derive(Serialize, Deserialize)]
-struct LoginResponse<'a> {
-    name: &'a str,
+pub(super) struct SessionClaims {
+    pub(super) name: String,
 }
 
 #[debug_handler]
@@ -28,79 +37,123 @@ pub(super) async fn login(
     State(state): State<AppState>,
     Query(payload): Query<LoginBotPayload>,
     web_socket: WebSocketUpgrade,
-) -> Result<Token, LoginBotError> {
-    let connection = state.database.lock().await;
+) -> Result<Response, LoginBotError> {
+    if state.shutdown_phase() != ShutdownPhase::Running {
+        return Err(LoginBotError::ShuttingDown);
+    }
+
+    let queue = match payload.practice.as_deref() {
+        None => Queue::Ladder,
+        Some(opponent) if opponent == payload.name => {
+            return Err(LoginBotError::InvalidPracticeOpponent)
+        }
+        Some(opponent) => Queue::Practice(match opponent.strip_prefix(BUILTIN_PREFIX) {
+            Some(builtin) => PracticeOpponent::Builtin(
+                BuiltinBot::from_name(builtin).ok_or(LoginBotError::InvalidPracticeOpponent)?,
+            ),
+            None => PracticeOpponent::Bot(opponent.into()),
+        }),
+    };
+
+    let record = authenticate(&state, &payload.name, payload.password).await?;
 
     let secret: Arc<[u8]> = StdRng::from_os_rng()
         .sample_iter::<u8, _>(&StandardUniform)
         .take(32)
         .collect();
 
-    let (mut bot, hashed_password): (Bot, String) = connection
-        .query_row(
-            "SELECT id, name, elo, password FROM bots WHERE name = ?1",
-            params![payload.name],
-            |row| {
-                Ok((
-                    Bot {
-                        id: row.get(0)?,
-                        name: row.get(1)?,
-                        elo: row.get(2)?,
-                        socket: None,
-                        secret,
-                    },
-                    row.get(3)?,
-                ))
-            },
-        )
-        .optional()?
-        .ok_or(LoginBotError::InvalidName)?;
-
-    let parsed_hash = PasswordHash::new(&hashed_password)?;
-    if Argon2::default()
-        .verify_password(payload.password.as_bytes(), &parsed_hash)
-        .ok()
-        .is_none()
-    {
-        return Err(LoginBotError::InvalidPassword);
-    }
-
+    let mut bot = Bot {
+        id: record.id,
+        name: record.name.into(),
+        elo: record.elo,
+        socket: None,
+        secret,
+        reconnections: Arc::new(watch::channel(0).0),
+        queue,
+        builtin: None,
+    };
+
+    // Bots that were disconnected but are still in their grace period must resume their
+    // session instead.
     if state.pending_bots.lock().await.contains(&bot)
         || state.connected_bots.lock().await.contains(&bot)
+        || state.disconnected_bots.lock().await.contains_key(&bot.id)
     {
         return Err(LoginBotError::AlreadyLoggedIn);
     }
 
-    let response = LoginResponse { name: &bot.name };
+    let claims = SessionClaims {
+        name: bot.name.to_string(),
+    };
 
     let token = jsonwebtoken::encode(
         &jsonwebtoken::Header::default(),
-        &response,
+        &claims,
         &jsonwebtoken::EncodingKey::from_secret(&bot.secret),
     );
 
     let token = token.map_err(|_| LoginBotError::CouldNotEncodeToken)?;
 
-    let result = web_socket.on_upgrade(|socket| async move {
-        bot.socket = Some(Arc::new(Mutex::new(socket)));
+    // The upgrade response must be sent back for the web socket to be established, so the token
+    // is sent alongside it as a header. The token is what allows the bot to resume its session
+    // should its connection drop.
+    let response = web_socket.on_upgrade(|socket| async move {
+        bot.socket = Some(Arc::new(BotSocket::new(socket)));
+        state.mark_seen(bot.id);
+        state.publish(ServerEvent::BotConnected {
+            name: bot.name.to_string(),
+        });
         state.pending_bots.lock().await.push(bot);
     });
 
-    error!("the result was: {result:?}");
+    Ok(([(TOKEN_HEADER, token)], response).into_response())
+}
+
+/// Checks the credentials of a bot, returning its record if they are valid.
+pub(super) async fn authenticate(
+    state: &AppState,
+    name: &str,
+    password: String,
+) -> Result<BotRecord, LoginBotError> {
+    let record = state
+        .storage
+        .find_bot(name)
+        .await?
+        .ok_or(LoginBotError::InvalidName)?;
+
+    // Verifying the password is purposefully slow, so it is done on a blocking thread.
+    let hashed_password = record.password_hash.clone();
+    let is_password_valid = tokio::task::spawn_blocking(move || {
+        let parsed_hash = PasswordHash::new(&hashed_password)?;
+        Ok::<_, argon2::password_hash::Error>(
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed_hash)
+                .is_ok(),
+        )
+    })
+    .await??;
 
-    Ok(token)
+    if !is_password_valid {
+        return Err(LoginBotError::InvalidPassword);
+    }
+
+    Ok(record)
 }
 
 #[derive(Deserialize)]
 pub(super) struct LoginBotPayload {
     name: String,
     password: String,
+    /// Puts the bot in the practice queue against the given opponent (the name of a bot practicing
+    /// against this one in return, or of a built-in bot prefixed with [`BUILTIN_PREFIX`]) instead
+    /// of the ladder.
+    practice: Option<String>,
 }
 
 #[derive(Error, Debug)]
 pub(super) enum LoginBotError {
-    #[error("rusqlite error: {0}")]
-    DatabaseError(#[from] rusqlite::Error),
+    #[error("storage error: {0}")]
+    StorageError(#[from] StorageError),
 
     #[error("name is not in the database")]
     InvalidName,
@@ -111,11 +164,20 @@ pub(super) enum LoginBotError {
     #[error("argon2 error: {0}")]
     HasherError(argon2::password_hash::Error),
 
+    #[error("hashing task failed: {0}")]
+    TaskFailed(#[from] tokio::task::JoinError),
+
     #[error("bot already logged in.")]
     AlreadyLoggedIn,
 
     #[error("could not encode token")]
     CouldNotEncodeToken,
+
+    #[error("the server is shutting down")]
+    ShuttingDown,
+
+    #[error("unknown practice opponent")]
+    InvalidPracticeOpponent,
 }
 
 // Needed because argon2::password_has::Error doesn't implement std::error::Error 😤
@@ -131,9 +193,12 @@ impl IntoResponse for LoginBotError {
             Self::InvalidName => (StatusCode::UNAUTHORIZED, "invalid username"),
             Self::InvalidPassword => (StatusCode::UNAUTHORIZED, "invalid password"),
             Self::AlreadyLoggedIn => (StatusCode::UNAUTHORIZED, "already logged in"),
-            Self::DatabaseError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ""),
+            Self::StorageError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ""),
             Self::HasherError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "password is corrupted"),
+            Self::TaskFailed(_) => (StatusCode::INTERNAL_SERVER_ERROR, ""),
             Self::CouldNotEncodeToken => (StatusCode::INTERNAL_SERVER_ERROR, ""),
+            Self::ShuttingDown => (StatusCode::SERVICE_UNAVAILABLE, "server is shutting down"),
+            Self::InvalidPracticeOpponent => (StatusCode::BAD_REQUEST, "unknown practice opponent"),
         }
         .into_response()
     }
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use reqwest::StatusCode;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::server::{
+    app_state::{AppState, BUILTIN_PREFIX},
+    storage::{MatchOutcome, MatchResult, StorageError},
+};
+
+/// Lists the practice matches of a bot, newest first. They are kept apart from its profile as
+/// they don't count towards the ladder.
+#[debug_handler]
+pub(super) async fn practice_matches(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<PracticeMatch>>, PracticeError> {
+    let bots = state.storage.bots().await?;
+    let bot = bots
+        .iter()
+        .find(|bot| bot.name == name)
+        .ok_or(PracticeError::UnknownBot)?;
+    let names: HashMap<_, _> = bots.iter().map(|bot| (bot.id, bot.name.as_str())).collect();
+
+    let matches = state.storage.practice_matches_of(bot.id).await?;
+    let matches = matches
+        .into_iter()
+        .rev()
+        .map(|record| {
+            let seat = if record.players[0] == Some(bot.id) {
+                0
+            } else {
+                1
+            };
+            let opponent = match (record.players[1 - seat], &record.builtin) {
+                (Some(id), _) => names.get(&id).copied().unwrap_or("<unknown>").to_owned(),
+                (None, Some(builtin)) => format!("{BUILTIN_PREFIX}{builtin}"),
+                (None, None) => "<unknown>".to_owned(),
+            };
+
+            PracticeMatch {
+                id: record.id,
+                opponent,
+                seat: seat as u8,
+                result: record.outcome.result_for(seat as u8),
+                outcome: record.outcome,
+                finished_at: record.finished_at,
+            }
+        })
+        .collect();
+
+    Ok(Json(matches))
+}
+
+#[derive(Serialize)]
+pub(super) struct PracticeMatch {
+    id: u64,
+    opponent: String,
+    seat: u8,
+    result: MatchResult,
+    outcome: MatchOutcome,
+    finished_at: u64,
+}
+
+#[derive(Error, Debug)]
+pub(super) enum PracticeError {
+    #[error("no bot has this name")]
+    UnknownBot,
+
+    #[error("error whilst querying storage: {0}")]
+    StorageError(#[from] StorageError),
+}
+
+impl IntoResponse for PracticeError {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            Self::UnknownBot => (StatusCode::NOT_FOUND, "unknown bot"),
+            Self::StorageError(_error) => (StatusCode::INTERNAL_SERVER_ERROR, ""),
+        }
+        .into_response()
+    }
+}
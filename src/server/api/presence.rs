@@ -0,0 +1,47 @@
+use axum::{debug_handler, extract::State, Json};
+use serde::Serialize;
+
+use crate::server::app_state::AppState;
+
+/// Lists every logged in bot along with the last time it showed signs of life.
+#[debug_handler]
+pub(super) async fn presence(State(state): State<AppState>) -> Json<Vec<BotPresence>> {
+    let pending_bots: Vec<_> = state.pending_bots.lock().await.clone();
+    let connected_bots: Vec<_> = state.connected_bots.lock().await.iter().cloned().collect();
+
+    let presence = state.presence_lock();
+    let mut bots: Vec<_> = pending_bots
+        .into_iter()
+        .map(|bot| (bot, PresenceState::Pending))
+        .chain(
+            connected_bots
+                .into_iter()
+                .map(|bot| (bot, PresenceState::Connected)),
+        )
+        .map(|(bot, state)| BotPresence {
+            last_seen: presence.get(&bot.id).copied(),
+            name: bot.name.to_string(),
+            state,
+        })
+        .collect();
+    bots.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Json(bots)
+}
+
+#[derive(Serialize)]
+pub(super) struct BotPresence {
+    name: String,
+    state: PresenceState,
+    /// Unix timestamp (in seconds) of the last ping answered or move played by the bot.
+    last_seen: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum PresenceState {
+    /// Logged in, waiting to be paired with the rest of the ladder.
+    Pending,
+    /// Part of the ladder.
+    Connected,
+}
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use reqwest::StatusCode;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::server::{
+    app_state::AppState,
+    storage::{
+        BotRecord, BotStats, MatchOutcome, MatchRecord, MatchResult, StatsAccumulator, StorageError,
+    },
+};
+
+use super::leaderboard::online_bots;
+
+/// Amount of matches listed in a profile's recent matches.
+const RECENT_MATCH_COUNT: usize = 20;
+
+#[debug_handler]
+pub(super) async fn bot_profile(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<BotProfile>, BotProfileError> {
+    let bots = state.storage.bots().await?;
+    let bot = bots
+        .iter()
+        .find(|bot| bot.name == name)
+        .ok_or(BotProfileError::UnknownBot)?;
+
+    let matches = state.storage.matches_of(bot.id).await?;
+    let online = online_bots(&state).await.contains(&bot.id);
+    let last_seen = state.presence_lock().get(&bot.id).copied();
+
+    Ok(Json(build_profile(bot, online, last_seen, &bots, &matches)))
+}
+
+/// Computes the profile of a bot from every match it played (oldest first).
+pub(super) fn build_profile(
+    bot: &BotRecord,
+    online: bool,
+    last_seen: Option<u64>,
+    bots: &[BotRecord],
+    matches: &[MatchRecord],
+) -> BotProfile {
+    let names: HashMap<_, _> = bots.iter().map(|bot| (bot.id, bot.name.as_str())).collect();
+    let name_of = |id| names.get(&id).copied().unwrap_or("<unknown>").to_owned();
+
+    let mut stats = StatsAccumulator::default();
+    let mut seats = [Record::default(), Record::default()];
+    let mut head_to_head: HashMap<u16, Record> = HashMap::new();
+    let mut rating_history = Vec::with_capacity(matches.len());
+    let (mut margin_sum, mut margin_count) = (0i64, 0u32);
+    let (mut disqualifications, mut opponent_disqualifications) = (0, 0);
+
+    for record in matches {
+        // A bot could in theory play against itself, in which case the first seat is used.
+        let seat = if record.players[0] == bot.id { 0 } else { 1 };
+        let result = record.outcome.result_for(seat as u8);
+
+        stats.add(result, record.elos[seat]);
+        seats[seat].add(result);
+        head_to_head
+            .entry(record.players[1 - seat])
+            .or_default()
+            .add(result);
+        rating_history.push(RatingPoint {
+            match_id: record.id,
+            finished_at: record.finished_at,
+            elo: record.elos[seat],
+        });
+
+        match record.outcome {
+            MatchOutcome::Won { delta, .. } => {
+                margin_count += 1;
+                margin_sum += match result {
+                    MatchResult::Win => delta as i64,
+                    _ => -(delta as i64),
+                };
+            }
+            MatchOutcome::Disqualified { loser, .. } if loser as usize == seat => {
+                disqualifications += 1
+            }
+            MatchOutcome::Disqualified { .. } => opponent_disqualifications += 1,
+            MatchOutcome::Tie | MatchOutcome::Aborted => {}
+        }
+    }
+
+    let mut head_to_head: Vec<_> = head_to_head
+        .into_iter()
+        .map(|(opponent, record)| HeadToHead {
+            opponent: name_of(opponent),
+            record,
+        })
+        .collect();
+    head_to_head.sort_by(|a, b| a.opponent.cmp(&b.opponent));
+
+    let recent_matches = matches
+        .iter()
+        .rev()
+        .take(RECENT_MATCH_COUNT)
+        .map(|record| {
+            let seat = if record.players[0] == bot.id { 0 } else { 1 };
+            RecentMatch {
+                id: record.id,
+                opponent: name_of(record.players[1 - seat]),
+                seat: seat as u8,
+                result: record.outcome.result_for(seat as u8),
+                outcome: record.outcome,
+                elo: record.elos[seat],
+                finished_at: record.finished_at,
+            }
+        })
+        .collect();
+
+    BotProfile {
+        name: bot.name.clone(),
+        elo: bot.elo,
+        online,
+        last_seen,
+        stats: stats.finish(),
+        first_seat: seats[0].clone(),
+        second_seat: seats[1].clone(),
+        average_margin: (margin_count != 0).then(|| margin_sum as f64 / margin_count as f64),
+        disqualifications,
+        opponent_disqualifications,
+        rating_history,
+        head_to_head,
+        recent_matches,
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct BotProfile {
+    name: String,
+    elo: u16,
+    online: bool,
+    /// Unix timestamp (in seconds) of the last sign of life of the bot, if it is logged in.
+    last_seen: Option<u64>,
+    #[serde(flatten)]
+    stats: BotStats,
+    /// Results when the bot was in seat 0, which moves first unless the opening says otherwise.
+    first_seat: Record,
+    /// Results when the bot was in seat 1.
+    second_seat: Record,
+    /// Average score difference of matches that were played until the end without a tie,
+    /// positive when the bot won and negative when it lost. Null if there are no such matches.
+    average_margin: Option<f64>,
+    /// Amount of times this bot was disqualified.
+    disqualifications: u32,
+    /// Amount of times this bot won because its opponent was disqualified.
+    opponent_disqualifications: u32,
+    /// Elo of the bot after each of its matches, oldest first.
+    rating_history: Vec<RatingPoint>,
+    head_to_head: Vec<HeadToHead>,
+    /// Latest matches, newest first.
+    recent_matches: Vec<RecentMatch>,
+}
+
+#[derive(Serialize, Default, Clone)]
+struct Record {
+    games: u32,
+    wins: u32,
+    losses: u32,
+    ties: u32,
+    /// Ratio of games won, null if no game was played.
+    win_rate: Option<f64>,
+}
+
+impl Record {
+    fn add(&mut self, result: MatchResult) {
+        match result {
+            MatchResult::Win => self.wins += 1,
+            MatchResult::Loss => self.losses += 1,
+            MatchResult::Tie => self.ties += 1,
+            MatchResult::Aborted => return,
+        }
+        self.games += 1;
+        self.win_rate = Some(self.wins as f64 / self.games as f64);
+    }
+}
+
+#[derive(Serialize)]
+struct RatingPoint {
+    match_id: u64,
+    finished_at: u64,
+    elo: u16,
+}
+
+#[derive(Serialize)]
+struct HeadToHead {
+    opponent: String,
+    #[serde(flatten)]
+    record: Record,
+}
+
+#[derive(Serialize)]
+struct RecentMatch {
+    id: u64,
+    opponent: String,
+    seat: u8,
+    result: MatchResult,
+    outcome: MatchOutcome,
+    /// Elo of the bot once the match was over.
+    elo: u16,
+    finished_at: u64,
+}
+
+#[derive(Error, Debug)]
+pub(super) enum BotProfileError {
+    #[error("no bot has this name")]
+    UnknownBot,
+
+    #[error("error whilst querying storage: {0}")]
+    StorageError(#[from] StorageError),
+}
+
+impl IntoResponse for BotProfileError {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            Self::UnknownBot => (StatusCode::NOT_FOUND, "unknown bot"),
+            Self::StorageError(_error) => (StatusCode::INTERNAL_SERVER_ERROR, ""),
+        }
+        .into_response()
+    }
+}
@@ -1,4 +1,7 @@
-use crate::server::app_state::AppState;
+use crate::server::{
+    app_state::{AppState, BUILTIN_PREFIX},
+    storage::StorageError,
+};
 
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
@@ -11,7 +14,6 @@ use axum::{
 };
 
 use reqwest::StatusCode;
-use rusqlite::params;
 use serde::Deserialize;
 use thiserror::Error;
 
@@ -20,28 +22,38 @@ pub(super) async fn register_bot(
     State(state): State<AppState>,
     Query(payload): Query<RegisterBotPayload>,
 ) -> Result<(), RegisterBotError> {
-    let connection = state.database.lock().await;
-
-    let column_count: usize = connection.query_row(
-        "SELECT COUNT(*) FROM bots WHERE name = ?1",
-        params![payload.name],
-        |row| row.get(0),
-    )?;
+    // Built-in bots are chosen by name when practicing, so their names must stay unambiguous.
+    if payload.name.starts_with(BUILTIN_PREFIX) {
+        return Err(RegisterBotError::ReservedName);
+    }
 
-    if column_count != 0 {
+    // Checked early to avoid hashing the password for nothing. Insertion still fails if the name
+    // got taken in the meantime.
+    if state.storage.find_bot(&payload.name).await?.is_some() {
         return Err(RegisterBotError::NameInUse);
     }
 
-    let password = payload.password.as_bytes();
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-
-    let hashed_password = argon2.hash_password(password, &salt)?.to_string();
+    // Hashing is purposefully slow, so it is done on a blocking thread.
+    let password = payload.password;
+    let hashed_password = tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+    })
+    .await??;
 
-    connection.execute(
-        "INSERT INTO bots (name, password, elo) VALUES (?1, ?2, 1000)",
-        params![payload.name, hashed_password],
-    )?;
+    if !state
+        .storage
+        .insert_bot(
+            &payload.name,
+            &hashed_password,
+            state.settings().matchmaking.starting_elo,
+        )
+        .await?
+    {
+        return Err(RegisterBotError::NameInUse);
+    }
 
     Ok(())
 }
@@ -57,11 +69,17 @@ pub(super) enum RegisterBotError {
     #[error("name is already registed.")]
     NameInUse,
 
-    #[error("rusqlite error: {0}")]
-    DatabaseError(#[from] rusqlite::Error),
+    #[error("name is reserved for built-in bots")]
+    ReservedName,
+
+    #[error("storage error: {0}")]
+    StorageError(#[from] StorageError),
 
     #[error("argon2 error: {0}")]
     HasherError(argon2::password_hash::Error),
+
+    #[error("hashing task failed: {0}")]
+    TaskFailed(#[from] tokio::task::JoinError),
 }
 
 // Needed because argon2::password_has::Error doesn't implement std::error::Error 😤
@@ -75,8 +93,13 @@ impl IntoResponse for RegisterBotError {
     fn into_response(self) -> axum::response::Response {
         match self {
             Self::NameInUse => (StatusCode::UNAUTHORIZED, "name is already taken"),
-            Self::DatabaseError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ""),
+            Self::ReservedName => (
+                StatusCode::BAD_REQUEST,
+                "name is reserved for built-in bots",
+            ),
+            Self::StorageError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ""),
             Self::HasherError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ""),
+            Self::TaskFailed(_) => (StatusCode::INTERNAL_SERVER_ERROR, ""),
         }
         .into_response()
     }
@@ -0,0 +1,128 @@
+use axum::{
+    debug_handler,
+    extract::{ws::WebSocket, Query, State, WebSocketUpgrade},
+    response::{IntoResponse, Response},
+};
+use jsonwebtoken::{DecodingKey, Validation};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::time::Instant;
+use tracing::trace;
+
+use crate::server::{
+    app_state::{AppState, Bot},
+    events::ServerEvent,
+};
+
+use super::login::SessionClaims;
+
+/// Rebinds a logged in bot (or one whose connection dropped less than the grace period ago) to
+/// a new web socket, given the session token it received when logging in. Matches the bot is
+/// playing carry on with the new socket, starting by sending it their current position again.
+#[debug_handler]
+pub(super) async fn resume(
+    State(state): State<AppState>,
+    Query(payload): Query<ResumePayload>,
+    web_socket: WebSocketUpgrade,
+) -> Result<Response, ResumeError> {
+    let bot = find_session(&state, &payload.name)
+        .await
+        .ok_or(ResumeError::UnknownSession)?;
+
+    // The session token has no expiration date, as it is only valid for as long as the
+    // session (and thus the secret it is signed with) is.
+    let mut validation = Validation::default();
+    validation.required_spec_claims.clear();
+    validation.validate_exp = false;
+
+    let claims = jsonwebtoken::decode::<SessionClaims>(
+        &payload.token,
+        &DecodingKey::from_secret(&bot.secret),
+        &validation,
+    )
+    .map_err(|_| ResumeError::InvalidToken)?
+    .claims;
+
+    if claims.name != *bot.name {
+        return Err(ResumeError::InvalidToken);
+    }
+
+    Ok(web_socket.on_upgrade(move |socket| rebind(state, bot, socket)))
+}
+
+/// Finds the session of the bot with the given name, whether it is still logged in or was
+/// disconnected less than the grace period ago.
+async fn find_session(state: &AppState, name: &str) -> Option<Bot> {
+    let is_named = |bot: &&Bot| *bot.name == *name;
+
+    if let Some(bot) = state.connected_bots.lock().await.iter().find(is_named) {
+        return Some(bot.clone());
+    }
+
+    if let Some(bot) = state.pending_bots.lock().await.iter().find(is_named) {
+        return Some(bot.clone());
+    }
+
+    state
+        .disconnected_bots
+        .lock()
+        .await
+        .values()
+        .find(|disconnected| disconnected.deadline > Instant::now() && is_named(&&disconnected.bot))
+        .map(|disconnected| disconnected.bot.clone())
+}
+
+async fn rebind(state: AppState, bot: Bot, socket: WebSocket) {
+    let Some(current_socket) = bot.socket.clone() else {
+        return;
+    };
+
+    // Every clone of the bot (including the ones in running matches) shares the socket, so
+    // attaching the new web socket to it is enough to rebind all of them. Queries waiting for
+    // an answer on the previous web socket are told it closed, and send their position again.
+    current_socket.attach(socket);
+    state.mark_seen(bot.id);
+
+    let disconnected = state.disconnected_bots.lock().await.remove(&bot.id);
+    if let Some(disconnected) = disconnected {
+        if disconnected.was_connected {
+            state.connected_bots.lock().await.insert(bot.clone());
+        } else {
+            state.pending_bots.lock().await.push(bot.clone());
+        }
+
+        state.publish(ServerEvent::BotConnected {
+            name: bot.name.to_string(),
+        });
+    }
+
+    // Wakes up the matches waiting for the bot to come back.
+    bot.reconnections.send_modify(|count| *count += 1);
+    trace!("Bot {} resumed its session.", bot.name);
+}
+
+#[derive(Deserialize)]
+pub(super) struct ResumePayload {
+    name: String,
+    token: String,
+}
+
+#[derive(Error, Debug)]
+pub(super) enum ResumeError {
+    #[error("the bot has no session to resume")]
+    UnknownSession,
+
+    #[error("the session token is invalid")]
+    InvalidToken,
+}
+
+impl IntoResponse for ResumeError {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            Self::UnknownSession => (StatusCode::UNAUTHORIZED, "no session to resume"),
+            Self::InvalidToken => (StatusCode::UNAUTHORIZED, "invalid token"),
+        }
+        .into_response()
+    }
+}
@@ -0,0 +1,159 @@
+use axum::{
+    debug_handler,
+    extract::{
+        ws::{Message, WebSocket},
+        Query, State, WebSocketUpgrade,
+    },
+    response::{IntoResponse, Response},
+    Json,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::{
+    mancala::Game,
+    server::{
+        app_state::{AppState, Match},
+        spectators::SpectatorEvent,
+    },
+};
+
+/// Lists every match currently being played.
+#[debug_handler]
+pub(super) async fn running_matches(State(state): State<AppState>) -> Json<Vec<RunningMatch>> {
+    let mut matches: Vec<_> = state
+        .running_matches_lock()
+        .values()
+        .map(RunningMatch::from)
+        .collect();
+    matches.sort_by_key(|running_match| running_match.id);
+
+    Json(matches)
+}
+
+/// Opens a web socket through which the events of a running match (or all of them if no match
+/// is specified) are sent, as JSON, as they happen.
+#[debug_handler]
+pub(super) async fn spectate(
+    State(state): State<AppState>,
+    Query(query): Query<SpectateQuery>,
+    web_socket: WebSocketUpgrade,
+) -> Result<Response, SpectateError> {
+    if let Some(match_id) = query.match_id {
+        if !state.running_matches_lock().contains_key(&match_id) {
+            return Err(SpectateError::UnknownMatch);
+        }
+    }
+
+    Ok(web_socket.on_upgrade(move |socket| stream_events(state, query.match_id, socket)))
+}
+
+async fn stream_events(state: AppState, match_id: Option<u64>, mut socket: WebSocket) {
+    let is_followed = |id| match_id.is_none_or(|match_id| match_id == id);
+
+    // Subscribing before looking at the running matches ensures no event is missed in between
+    // (at worst, a move is sent twice).
+    let mut events = state.spectators.subscribe();
+
+    // Spectators joining mid-match are first sent the current state of each followed match.
+    let snapshot: Vec<_> = state
+        .running_matches_lock()
+        .values()
+        .filter(|running_match| is_followed(running_match.id))
+        .map(|running_match| SpectatorEvent::Started {
+            match_id: running_match.id,
+            players: running_match
+                .players
+                .clone()
+                .map(|bot| bot.name.to_string()),
+            game: running_match.game.clone(),
+        })
+        .collect();
+
+    for event in snapshot {
+        if send_event(&mut socket, &event).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) if is_followed(event.match_id()) => {
+                    if send_event(&mut socket, &event).await.is_err() {
+                        return;
+                    }
+
+                    // There is nothing left to follow once the only followed match is over.
+                    if match_id.is_some() && matches!(event, SpectatorEvent::Finished { .. }) {
+                        let _ = socket.send(Message::Close(None)).await;
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(count)) => {
+                    warn!("A spectator fell behind and missed {count} events.");
+                }
+                Err(RecvError::Closed) => return,
+            },
+
+            // Spectators have nothing to say, anything but the socket closing is ignored.
+            message = socket.recv() => match message {
+                None | Some(Err(_)) | Some(Ok(Message::Close(_))) => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn send_event(socket: &mut WebSocket, event: &SpectatorEvent) -> Result<(), axum::Error> {
+    // Serializing events can't fail, they only contain strings and numbers.
+    let payload = serde_json::to_string(event).unwrap_or_default();
+    socket.send(payload.into()).await
+}
+
+#[derive(Deserialize)]
+pub(super) struct SpectateQuery {
+    #[serde(rename = "match")]
+    match_id: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub(super) struct RunningMatch {
+    id: u64,
+    players: [String; 2],
+    game: Game,
+    started_at: u64,
+}
+
+impl From<&Match> for RunningMatch {
+    fn from(running_match: &Match) -> Self {
+        Self {
+            id: running_match.id,
+            players: running_match
+                .players
+                .clone()
+                .map(|bot| bot.name.to_string()),
+            game: running_match.game.clone(),
+            started_at: running_match.started_at,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub(super) enum SpectateError {
+    #[error("no running match has this id")]
+    UnknownMatch,
+}
+
+impl IntoResponse for SpectateError {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            Self::UnknownMatch => (StatusCode::NOT_FOUND, "unknown match"),
+        }
+        .into_response()
+    }
+}
@@ -0,0 +1,114 @@
+#![cfg(test)]
+
+use serde_json::{json, Value};
+
+use super::profile::build_profile;
+use crate::server::storage::{BotRecord, MatchOutcome, MatchRecord};
+
+fn bot(id: u16, name: &str) -> BotRecord {
+    BotRecord {
+        id,
+        name: name.to_owned(),
+        elo: 1000,
+        password_hash: String::new(),
+    }
+}
+
+fn played(id: u64, players: [u16; 2], outcome: MatchOutcome) -> MatchRecord {
+    MatchRecord {
+        id,
+        players,
+        outcome,
+        elos: [1000 + id as u16; 2],
+        finished_at: id,
+    }
+}
+
+#[test]
+fn profiles_are_computed_from_the_bot_point_of_view() {
+    let bots = [bot(1, "a"), bot(2, "b"), bot(3, "c")];
+    let matches = [
+        played(
+            1,
+            [1, 2],
+            MatchOutcome::Won {
+                winner: 0,
+                delta: 6,
+            },
+        ),
+        played(
+            2,
+            [2, 1],
+            MatchOutcome::Won {
+                winner: 0,
+                delta: 4,
+            },
+        ),
+        played(3, [1, 3], MatchOutcome::Tie),
+        played(
+            4,
+            [3, 1],
+            MatchOutcome::Disqualified {
+                loser: 0,
+                reason: None,
+            },
+        ),
+        played(
+            5,
+            [1, 2],
+            MatchOutcome::Disqualified {
+                loser: 0,
+                reason: None,
+            },
+        ),
+        // Aborted matches don't count towards any record.
+        played(6, [1, 2], MatchOutcome::Aborted),
+    ];
+
+    let profile = build_profile(&bots[0], true, Some(42), &bots, &matches);
+    let profile = serde_json::to_value(profile).unwrap();
+
+    let record = |games, wins, losses, ties, win_rate: f64| json!({ "games": games, "wins": wins, "losses": losses, "ties": ties, "win_rate": win_rate });
+    assert_eq!(profile["first_seat"], record(3, 1, 1, 1, 1.0 / 3.0));
+    assert_eq!(profile["second_seat"], record(2, 1, 1, 0, 0.5));
+    assert_eq!(profile["average_margin"], json!(1.0));
+    assert_eq!(profile["disqualifications"], json!(1));
+    assert_eq!(profile["opponent_disqualifications"], json!(1));
+
+    let mut b = record(3, 1, 2, 0, 1.0 / 3.0);
+    b["opponent"] = json!("b");
+    let mut c = record(2, 1, 0, 1, 0.5);
+    c["opponent"] = json!("c");
+    assert_eq!(profile["head_to_head"], json!([b, c]));
+
+    assert_eq!(
+        [
+            &profile["games"],
+            &profile["wins"],
+            &profile["losses"],
+            &profile["ties"]
+        ],
+        [&json!(5), &json!(2), &json!(2), &json!(1)]
+    );
+
+    let recent = profile["recent_matches"].as_array().unwrap();
+    assert_eq!(recent.len(), 6);
+    assert_eq!(recent[0]["id"], json!(6));
+    assert_eq!(recent[1]["opponent"], json!("b"));
+    assert_eq!(recent[2]["seat"], json!(1));
+    assert_eq!(recent[2]["result"], json!("win"));
+    assert_eq!(
+        profile["rating_history"][1],
+        json!({ "match_id": 2, "finished_at": 2, "elo": 1002 })
+    );
+}
+
+#[test]
+fn profiles_without_matches_have_no_rates() {
+    let bots = [bot(1, "a")];
+    let profile = serde_json::to_value(build_profile(&bots[0], false, None, &bots, &[])).unwrap();
+
+    assert_eq!(profile["first_seat"]["win_rate"], Value::Null);
+    assert_eq!(profile["average_margin"], Value::Null);
+    assert_eq!(profile["head_to_head"], json!([]));
+}
@@ -1,20 +1,109 @@
-use std::{collections::HashSet, hash::Hash, path::Path, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU32, AtomicU64},
+        Arc,
+    },
+};
 
-use axum::extract::ws::WebSocket;
-use tokio::sync::Mutex;
+use tokio::{
+    sync::{broadcast, watch, Mutex},
+    time::Instant,
+};
 
 use reqwest::Client;
-use tracing::error;
 
-use crate::mancala::Game;
+use crate::{
+    config::Settings,
+    mancala::{
+        builtin::BuiltinBot,
+        play_match::{PlayerConnection, RemotePlayer},
+        Game,
+    },
+    server::{
+        bot_socket::BotSocket,
+        events::{ServerEvent, EVENT_BUFFER_SIZE},
+        shutdown::ShutdownPhase,
+        spectators::{SpectatorEvent, SPECTATOR_BUFFER_SIZE},
+        storage::{unix_timestamp, Storage},
+    },
+};
+
+/// Prefix of the names of the bots played by the server, which can't be registered.
+pub const BUILTIN_PREFIX: &str = "builtin:";
 
 #[derive(Clone, Debug)]
 pub struct Bot {
     pub name: Arc<str>,
     pub id: u16,
     pub elo: u16,
-    pub socket: Option<Arc<Mutex<WebSocket>>>,
+    pub socket: Option<Arc<BotSocket>>,
     pub secret: Arc<[u8]>,
+    /// Incremented each time the bot resumes its session on a new socket, see
+    /// [`crate::mancala::play_match::RemotePlayer`].
+    pub reconnections: Arc<watch::Sender<u64>>,
+    /// Which matches the bot plays, as chosen at login.
+    pub queue: Queue,
+    /// Set for bots played by the server itself, which never log in.
+    pub builtin: Option<BuiltinBot>,
+}
+
+impl Bot {
+    /// Creates a bot played by the server. Built-in bots are not stored, so they all share the
+    /// id 0 which no stored bot has, and never take part in rated matches.
+    pub fn builtin(builtin: BuiltinBot) -> Self {
+        Self {
+            name: format!("{BUILTIN_PREFIX}{}", builtin.name()).into(),
+            id: 0,
+            elo: 0,
+            socket: None,
+            secret: Arc::new([]),
+            reconnections: Arc::new(watch::channel(0).0),
+            queue: Queue::Ladder,
+            builtin: Some(builtin),
+        }
+    }
+
+    /// Returns the bot's connection, as used by play_match, if it has a socket (or is played by
+    /// the server).
+    pub fn connection(&self) -> Option<PlayerConnection> {
+        if let Some(builtin) = self.builtin {
+            return Some(PlayerConnection::Builtin(builtin));
+        }
+
+        Some(PlayerConnection::Remote(RemotePlayer {
+            socket: self.socket.clone()?,
+            reconnections: self.reconnections.subscribe(),
+        }))
+    }
+}
+
+/// Which matches a bot plays.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Queue {
+    /// Rated matches against the other bots of the ladder.
+    #[default]
+    Ladder,
+    /// Unrated matches against the given opponent, which don't affect the ladder.
+    Practice(PracticeOpponent),
+}
+
+/// Who a bot practices against.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PracticeOpponent {
+    /// Another logged in bot, by name, which must practice against this one in return.
+    Bot(Arc<str>),
+    Builtin(BuiltinBot),
+}
+
+/// A bot whose connection dropped, which can still resume its session until the deadline.
+#[derive(Clone)]
+pub struct DisconnectedBot {
+    pub bot: Bot,
+    /// Whether the bot was part of the ladder (as opposed to still pending) when it dropped.
+    pub was_connected: bool,
+    pub deadline: Instant,
 }
 
 impl Eq for Bot {}
@@ -31,10 +120,14 @@ impl Hash for Bot {
     }
 }
 
+/// A match that is currently being played.
 #[derive(Clone)]
 pub struct Match {
+    pub id: u64,
     pub game: Game,
     pub players: [Bot; 2],
+    /// Unix timestamp (in seconds) of the start of the match.
+    pub started_at: u64,
 }
 
 #[derive(Clone)]
@@ -42,42 +135,142 @@ pub struct AppState {
     // For sending messages to clients
     pub client: Client,
 
-    pub database: Arc<Mutex<rusqlite::Connection>>,
+    pub storage: Arc<dyn Storage>,
 
     pub pending_bots: Arc<Mutex<Vec<Bot>>>,
     pub connected_bots: Arc<Mutex<HashSet<Bot>>>,
+    /// Bots whose connection dropped but that may still resume their session, by bot id.
+    pub disconnected_bots: Arc<Mutex<HashMap<u16, DisconnectedBot>>>,
+
+    /// Held whilst the ratings of a match's players are updated, as they are read and written
+    /// separately and concurrent matches would otherwise overwrite each other's changes.
+    pub ratings: Arc<Mutex<()>>,
+
+    /// Matches currently being played, by id. This uses a std mutex as it is updated from
+    /// within play_match's synchronous callback, and is never held across an await point.
+    pub running_matches: Arc<std::sync::Mutex<HashMap<u64, Match>>>,
+    pub next_match_id: Arc<AtomicU64>,
+
+    /// Ids of the bots playing a series, which the matchmaker leaves alone until it is over, see
+    /// [`crate::matchmaker::play_series`]. Like running_matches, this is never held across an
+    /// await point.
+    pub series_bots: Arc<std::sync::Mutex<HashSet<u16>>>,
+
+    /// Every event of every running match is sent through this channel, each spectator filters
+    /// out the ones it is not interested in.
+    pub spectators: broadcast::Sender<SpectatorEvent>,
+
+    /// Unix timestamp (in seconds) of the last time each logged in bot showed signs of life, by
+    /// bot id. Like running_matches, this is updated from play_match's synchronous callback.
+    pub presence: Arc<std::sync::Mutex<HashMap<u16, u64>>>,
+
+    /// Lobby and ladder events, see [`AppState::publish`].
+    pub events: broadcast::Sender<ServerEvent>,
+
+    /// Where the server is in its shutdown sequence.
+    pub shutdown: Arc<watch::Sender<ShutdownPhase>>,
+
+    /// When the matchmaker last ran a round, see [`crate::server::health`].
+    pub last_matchmaking_round: Arc<std::sync::Mutex<Option<Instant>>>,
+    /// How many times in a row the matchmaker failed, see [`crate::matchmaker::supervise_matches`].
+    pub matchmaker_failures: Arc<AtomicU32>,
+
+    /// The part of the configuration that can change whilst the server is running, see
+    /// [`crate::config::reload_on_hangup`].
+    pub settings: Arc<watch::Sender<Settings>>,
 }
 
 impl AppState {
-    pub fn new(database_path: &Path) -> Self {
-        let database = match open_database(database_path) {
-            Ok(database) => database,
-            Err(error) => {
-                error!("Could not load in database due to following error: \"{error}\", shutting down server.");
-                std::process::exit(1);
-            }
-        };
+    pub fn new(storage: Arc<dyn Storage>, settings: Settings) -> Self {
         Self {
             client: Default::default(),
-            database: Arc::new(Mutex::new(database)),
+            storage,
             pending_bots: Arc::new(Mutex::new(Vec::new())),
             connected_bots: Arc::new(Mutex::new(HashSet::new())),
+            disconnected_bots: Arc::new(Mutex::new(HashMap::new())),
+            ratings: Default::default(),
+            running_matches: Default::default(),
+            presence: Default::default(),
+            next_match_id: Arc::new(AtomicU64::new(1)),
+            series_bots: Default::default(),
+            spectators: broadcast::channel(SPECTATOR_BUFFER_SIZE).0,
+            events: broadcast::channel(EVENT_BUFFER_SIZE).0,
+            shutdown: Arc::new(watch::channel(ShutdownPhase::Running).0),
+            last_matchmaking_round: Default::default(),
+            matchmaker_failures: Default::default(),
+            settings: Arc::new(watch::channel(settings).0),
         }
     }
-}
 
-fn open_database(path: &Path) -> rusqlite::Result<rusqlite::Connection> {
-    let database = rusqlite::Connection::open(path)?;
+    /// Sends an event to everyone listening to the server's events.
+    pub fn publish(&self, event: ServerEvent) {
+        // Sending only fails if nobody is listening, which is fine.
+        let _ = self.events.send(event);
+    }
+
+    /// Locks the running matches. A poisoned lock is recovered from, as the map can't be left
+    /// in an inconsistent state.
+    pub fn running_matches_lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Match>> {
+        self.running_matches
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Locks the presence map, see [`AppState::running_matches_lock`].
+    pub fn presence_lock(&self) -> std::sync::MutexGuard<'_, HashMap<u16, u64>> {
+        self.presence
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
 
-    let query = "
-            CREATE TABLE IF NOT EXISTS bots (
-                id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL UNIQUE,
-                password TEXT NOT NULL,
-                elo INTEGER
-            )
-        ";
-    database.execute(query, [])?;
+    /// Returns the current settings. They may change at any time, so they should be fetched
+    /// again rather than kept around.
+    pub fn settings(&self) -> Settings {
+        *self.settings.borrow()
+    }
 
-    Ok(database)
+    /// Returns where the server is in its shutdown sequence.
+    pub fn shutdown_phase(&self) -> ShutdownPhase {
+        *self.shutdown.borrow()
+    }
+
+    /// Moves the shutdown sequence forward to the given phase.
+    pub fn enter_phase(&self, phase: ShutdownPhase) {
+        self.shutdown.send_if_modified(|current| {
+            let is_later = phase > *current;
+            if is_later {
+                *current = phase;
+            }
+            is_later
+        });
+    }
+
+    /// Resolves once the shutdown sequence reaches the given phase.
+    pub async fn reached(&self, phase: ShutdownPhase) {
+        // The sender lives as long as the state, so waiting can't fail.
+        let _ = self
+            .shutdown
+            .subscribe()
+            .wait_for(|current| *current >= phase)
+            .await;
+    }
+
+    /// Locks the bots playing a series, see [`AppState::running_matches_lock`].
+    pub fn series_bots_lock(&self) -> std::sync::MutexGuard<'_, HashSet<u16>> {
+        self.series_bots
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Locks the time of the last matchmaking round, see [`AppState::running_matches_lock`].
+    pub fn last_matchmaking_round_lock(&self) -> std::sync::MutexGuard<'_, Option<Instant>> {
+        self.last_matchmaking_round
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Records that the bot with the given id just showed signs of life.
+    pub fn mark_seen(&self, id: u16) {
+        self.presence_lock().insert(id, unix_timestamp());
+    }
 }
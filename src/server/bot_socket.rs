@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use thiserror::Error;
+use tokio::sync::{mpsc, watch, Mutex, MutexGuard};
+
+/// Amount of messages a bot can send without being asked before the extra ones are dropped.
+const INCOMING_CAPACITY: usize = 16;
+
+/// A bot's connection to the server, which outlives the web sockets it goes through as resuming
+/// a session attaches a new web socket to it. Each web socket is driven by its own task, so that
+/// pongs are noticed (and messages sent) however long a query waits for its answer.
+#[derive(Debug)]
+pub struct BotSocket {
+    connection: std::sync::Mutex<Connection>,
+    incoming_sender: mpsc::Sender<Incoming>,
+    /// Messages received from the bot, which only the query being run may read.
+    incoming: Mutex<mpsc::Receiver<Incoming>>,
+    /// Incremented each time the bot answers a ping.
+    pongs: watch::Sender<u64>,
+}
+
+/// The web socket the bot currently uses.
+#[derive(Debug)]
+struct Connection {
+    /// Incremented each time a web socket is attached.
+    generation: u64,
+    outgoing: mpsc::UnboundedSender<Message>,
+}
+
+/// A message received on one of the bot's web sockets, None meaning that the web socket closed.
+#[derive(Debug)]
+struct Incoming {
+    generation: u64,
+    message: Option<Message>,
+}
+
+/// The bot's web socket closed, and no other was attached since.
+#[derive(Error, Debug)]
+#[error("the bot's web socket is closed")]
+pub struct SocketClosed;
+
+impl BotSocket {
+    pub fn new(socket: WebSocket) -> Self {
+        let (incoming_sender, incoming) = mpsc::channel(INCOMING_CAPACITY);
+        let bot_socket = Self {
+            connection: std::sync::Mutex::new(Connection {
+                generation: 0,
+                outgoing: mpsc::unbounded_channel().0,
+            }),
+            incoming_sender,
+            incoming: Mutex::new(incoming),
+            pongs: watch::channel(0).0,
+        };
+        bot_socket.attach(socket);
+
+        bot_socket
+    }
+
+    fn connection_lock(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Replaces the web socket the bot uses, closing the previous one.
+    pub fn attach(&self, socket: WebSocket) {
+        let (outgoing, outgoing_receiver) = mpsc::unbounded_channel();
+
+        let mut connection = self.connection_lock();
+        connection.generation += 1;
+        // Dropping the previous sender ends the task driving the previous web socket.
+        connection.outgoing = outgoing;
+
+        tokio::spawn(drive(
+            socket,
+            connection.generation,
+            outgoing_receiver,
+            self.incoming_sender.clone(),
+            self.pongs.clone(),
+        ));
+    }
+
+    /// Queues the message to be sent on the current web socket.
+    pub fn send(&self, message: Message) -> Result<(), SocketClosed> {
+        self.connection_lock()
+            .outgoing
+            .send(message)
+            .map_err(|_| SocketClosed)
+    }
+
+    /// Closes the current web socket, telling the bot why if a reason is given.
+    pub fn close(&self, frame: Option<CloseFrame>) {
+        // The bot may already be gone, which is fine.
+        let _ = self.send(Message::Close(frame));
+    }
+
+    /// Notified each time the bot answers a ping.
+    pub fn pongs(&self) -> watch::Receiver<u64> {
+        self.pongs.subscribe()
+    }
+
+    /// Pings the bot, returning whether it answered in time.
+    pub async fn ping(&self, timeout: Duration) -> bool {
+        let mut pongs = self.pongs();
+        if self.send(Message::Ping(Default::default())).is_err() {
+            return false;
+        }
+
+        matches!(
+            tokio::time::timeout(timeout, pongs.changed()).await,
+            Ok(Ok(()))
+        )
+    }
+
+    /// Waits for exclusive access to the bot's answers, so that concurrent matches don't read
+    /// each other's. Anything the bot sent beforehand is dropped, as it can't be an answer.
+    pub async fn exchange(&self) -> Exchange<'_> {
+        let mut incoming = self.incoming.lock().await;
+        while incoming.try_recv().is_ok() {}
+
+        Exchange {
+            socket: self,
+            incoming,
+            generation: self.connection_lock().generation,
+        }
+    }
+}
+
+/// Exclusive access to the messages of a bot, held for the duration of a query.
+pub struct Exchange<'a> {
+    socket: &'a BotSocket,
+    incoming: MutexGuard<'a, mpsc::Receiver<Incoming>>,
+    /// Web socket the last message was sent on, which the answer is expected from.
+    generation: u64,
+}
+
+impl Exchange<'_> {
+    /// Sends the message on the current web socket, whose answers are then the only ones read.
+    pub fn send(&mut self, message: Message) -> Result<(), SocketClosed> {
+        let connection = self.socket.connection_lock();
+        self.generation = connection.generation;
+        connection.outgoing.send(message).map_err(|_| SocketClosed)
+    }
+
+    /// Receives the next message sent on the web socket the last message was sent on, or None
+    /// once that web socket closed (including when it was replaced).
+    pub async fn recv(&mut self) -> Option<Message> {
+        loop {
+            // The sender is owned by the socket, so the channel never closes.
+            let incoming = self.incoming.recv().await?;
+            if incoming.generation == self.generation {
+                return incoming.message;
+            }
+        }
+    }
+}
+
+/// Sends and receives the messages of one web socket, until either side closes it.
+async fn drive(
+    mut socket: WebSocket,
+    generation: u64,
+    mut outgoing: mpsc::UnboundedReceiver<Message>,
+    incoming: mpsc::Sender<Incoming>,
+    pongs: watch::Sender<u64>,
+) {
+    loop {
+        tokio::select! {
+            message = outgoing.recv() => {
+                // The web socket was replaced, or the bot is gone for good.
+                let Some(message) = message else {
+                    break;
+                };
+
+                let is_close = matches!(message, Message::Close(_));
+                if socket.send(message).await.is_err() || is_close {
+                    break;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Pong(_))) => pongs.send_modify(|count| *count += 1),
+                // Pings are answered by axum itself.
+                Some(Ok(Message::Ping(_))) => {}
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Bots flooding the server lose the extra messages rather than filling memory.
+                Some(Ok(message)) => {
+                    let _ = incoming.try_send(Incoming {
+                        generation,
+                        message: Some(message),
+                    });
+                }
+            },
+        }
+    }
+
+    // Queries waiting for an answer are told that it won't come. This may wait for the queue
+    // to have room, so the web socket is closed first.
+    drop(socket);
+    let _ = incoming
+        .send(Incoming {
+            generation,
+            message: None,
+        })
+        .await;
+}
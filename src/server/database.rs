@@ -0,0 +1,465 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use async_trait::async_trait;
+use rusqlite::{params, DatabaseName, OpenFlags, OptionalExtension};
+use thiserror::Error;
+use tracing::info;
+
+use crate::server::storage::{
+    deviation, BotRecord, BotStats, MatchOutcome, MatchRecord, NewMatch, NewPracticeMatch,
+    PracticeMatchRecord, Storage, StorageError,
+};
+
+pub mod migrations;
+mod pool;
+mod tests;
+
+/// Sqlite implementation of the server's storage. It is trivialy cloneable, and all queries are
+/// run on a pool of connections so that concurrent requests and matches don't wait on one another.
+#[derive(Clone)]
+pub struct Database {
+    pool: Arc<pool::Pool>,
+}
+
+impl Database {
+    /// Opens the database at the given path, applying all pending migrations.
+    pub fn open(path: &Path) -> Result<Self, DatabaseError> {
+        Self::open_with_connections(path, pool::MAX_CONNECTIONS)
+    }
+
+    /// Same as [`Database::open`], but opening at most the given amount of connections at once.
+    pub fn open_with_connections(
+        path: &Path,
+        max_connections: usize,
+    ) -> Result<Self, DatabaseError> {
+        let connection = open_database(path)?;
+
+        Ok(Self {
+            pool: Arc::new(pool::Pool::new(path, connection, max_connections)?),
+        })
+    }
+}
+
+#[async_trait]
+impl Storage for Database {
+    async fn find_bot(&self, name: &str) -> Result<Option<BotRecord>, StorageError> {
+        let name = name.to_owned();
+        Ok(self
+            .pool
+            .run(move |connection| {
+                connection
+                    .query_row(
+                        "SELECT id, name, elo, password FROM bots WHERE name = ?1",
+                        params![name],
+                        bot_from_row,
+                    )
+                    .optional()
+            })
+            .await?)
+    }
+
+    async fn bots(&self) -> Result<Vec<BotRecord>, StorageError> {
+        Ok(self
+            .pool
+            .run(|connection| {
+                // Rows that can't be read (bots without an elo for instance) are skipped.
+                Ok(connection
+                    .prepare("SELECT id, name, elo, password FROM bots ORDER BY elo DESC")?
+                    .query_map([], bot_from_row)?
+                    .filter_map(|row| row.ok())
+                    .collect())
+            })
+            .await?)
+    }
+
+    async fn export_bots(&self, default_elo: u16) -> Result<Vec<BotRecord>, StorageError> {
+        Ok(self
+            .pool
+            .run(move |connection| {
+                connection
+                    .prepare("SELECT id, name, COALESCE(elo, ?1), password FROM bots ORDER BY id")?
+                    .query_map(params![default_elo], bot_from_row)?
+                    .collect()
+            })
+            .await?)
+    }
+
+    async fn insert_bot(
+        &self,
+        name: &str,
+        password_hash: &str,
+        elo: u16,
+    ) -> Result<bool, StorageError> {
+        let (name, password_hash) = (name.to_owned(), password_hash.to_owned());
+        Ok(self
+            .pool
+            .run(move |connection| {
+                connection.execute(
+                    "INSERT INTO bots (name, password, elo) VALUES (?1, ?2, ?3)
+                        ON CONFLICT (name) DO NOTHING",
+                    params![name, password_hash, elo],
+                )
+            })
+            .await?
+            == 1)
+    }
+
+    async fn import(
+        &self,
+        bots: Vec<BotRecord>,
+        matches: Vec<NewMatch>,
+        practice_matches: Vec<NewPracticeMatch>,
+    ) -> Result<(), StorageError> {
+        let taken_name = self
+            .pool
+            .run(move |connection| {
+                // Returning early drops the transaction, which rolls everything back.
+                let transaction = connection.transaction()?;
+
+                let mut ids = HashMap::new();
+                for bot in bots {
+                    let inserted = transaction.execute(
+                        "INSERT INTO bots (name, password, elo) VALUES (?1, ?2, ?3)
+                            ON CONFLICT (name) DO NOTHING",
+                        params![bot.name, bot.password_hash, bot.elo],
+                    )?;
+                    if inserted == 0 {
+                        return Ok(Some(bot.name));
+                    }
+                    ids.insert(bot.id, transaction.last_insert_rowid() as u16);
+                }
+
+                for mut record in matches {
+                    record.players = record.players.map(|id| ids[&id]);
+                    insert_match(&transaction, &record)?;
+                }
+                for mut record in practice_matches {
+                    record.players = record.players.map(|id| id.map(|id| ids[&id]));
+                    insert_practice_match(&transaction, &record)?;
+                }
+
+                transaction.commit()?;
+                Ok(None)
+            })
+            .await?;
+
+        match taken_name {
+            Some(name) => Err(StorageError::NameInUse(name)),
+            None => Ok(()),
+        }
+    }
+
+    async fn update_elo(&self, id: u16, elo: u16) -> Result<bool, StorageError> {
+        let updated_rows = self
+            .pool
+            .run(move |connection| {
+                connection.execute("UPDATE bots SET elo = ?1 WHERE id = ?2", params![elo, id])
+            })
+            .await?;
+
+        match updated_rows {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StorageError::Corrupted(format!(
+                "{updated_rows} bots share the id {id}"
+            ))),
+        }
+    }
+
+    async fn record_match(&self, record: NewMatch) -> Result<u64, StorageError> {
+        Ok(self
+            .pool
+            .run(move |connection| insert_match(connection, &record))
+            .await?)
+    }
+
+    async fn matches(&self) -> Result<Vec<MatchRecord>, StorageError> {
+        Ok(self
+            .pool
+            .run(|connection| {
+                connection
+                    .prepare(&format!("{SELECT_MATCHES} ORDER BY id"))?
+                    .query_map([], match_from_row)?
+                    .collect()
+            })
+            .await?)
+    }
+
+    async fn matches_of(&self, bot_id: u16) -> Result<Vec<MatchRecord>, StorageError> {
+        Ok(self
+            .pool
+            .run(move |connection| {
+                connection
+                    .prepare(&format!(
+                        "{SELECT_MATCHES} WHERE first_bot = ?1 OR second_bot = ?1 ORDER BY id"
+                    ))?
+                    .query_map(params![bot_id], match_from_row)?
+                    .collect()
+            })
+            .await?)
+    }
+
+    async fn record_practice_match(&self, record: NewPracticeMatch) -> Result<u64, StorageError> {
+        Ok(self
+            .pool
+            .run(move |connection| insert_practice_match(connection, &record))
+            .await?)
+    }
+
+    async fn practice_matches(&self) -> Result<Vec<PracticeMatchRecord>, StorageError> {
+        Ok(self
+            .pool
+            .run(|connection| {
+                connection
+                    .prepare(&format!("{SELECT_PRACTICE_MATCHES} ORDER BY id"))?
+                    .query_map([], practice_match_from_row)?
+                    .collect()
+            })
+            .await?)
+    }
+
+    async fn practice_matches_of(
+        &self,
+        bot_id: u16,
+    ) -> Result<Vec<PracticeMatchRecord>, StorageError> {
+        Ok(self
+            .pool
+            .run(move |connection| {
+                connection
+                    .prepare(&format!(
+                        "{SELECT_PRACTICE_MATCHES} WHERE first_bot = ?1 OR second_bot = ?1 \
+                         ORDER BY id"
+                    ))?
+                    .query_map(params![bot_id], practice_match_from_row)?
+                    .collect()
+            })
+            .await?)
+    }
+
+    async fn bot_stats(&self) -> Result<HashMap<u16, BotStats>, StorageError> {
+        let rows = self
+            .pool
+            .run(|connection| {
+                // Each match is split into one row per seat so that both players are aggregated
+                // the same way.
+                connection
+                    .prepare(
+                        "
+                        WITH seats AS (
+                            SELECT first_bot AS bot, 0 AS seat, outcome, winner,
+                                first_elo AS elo
+                            FROM matches
+                            UNION ALL
+                            SELECT second_bot, 1, outcome, winner, second_elo FROM matches
+                        )
+                        SELECT bot, COUNT(*),
+                            SUM(outcome != 'tie' AND winner = seat),
+                            SUM(outcome != 'tie' AND winner != seat),
+                            SUM(outcome = 'tie'),
+                            SUM(elo), SUM(elo * elo)
+                        FROM seats WHERE outcome != 'aborted' GROUP BY bot
+                        ",
+                    )?
+                    .query_map([], |row| {
+                        Ok((
+                            row.get::<_, u16>(0)?,
+                            [row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?],
+                            [row.get::<_, f64>(5)?, row.get::<_, f64>(6)?],
+                        ))
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(id, [games, wins, losses, ties], [elo_sum, elo_squared_sum])| {
+                    let stats = BotStats {
+                        games,
+                        wins,
+                        losses,
+                        ties,
+                        rating_deviation: deviation(games, elo_sum, elo_squared_sum),
+                    };
+                    (id, stats)
+                },
+            )
+            .collect())
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.pool
+            .run(|connection| connection.query_row("SELECT 1", [], |_| Ok(())))
+            .await?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), StorageError> {
+        // Moves everything from the write-ahead log back into the database file, so that the
+        // file is complete on its own once the server is gone.
+        self.pool
+            .run(|connection| {
+                connection.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+const SELECT_MATCHES: &str = "
+    SELECT id, first_bot, second_bot, outcome, winner, score_delta, first_elo, second_elo,
+        finished_at, disqualification_reason
+    FROM matches
+";
+
+fn match_from_row(row: &rusqlite::Row) -> rusqlite::Result<MatchRecord> {
+    let id = row.get(0)?;
+    let outcome = MatchOutcome::from_columns(
+        &row.get::<_, String>(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get::<_, Option<String>>(9)?.as_deref(),
+    )
+    .ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            3,
+            rusqlite::types::Type::Text,
+            format!("match {id} has an invalid outcome").into(),
+        )
+    })?;
+
+    Ok(MatchRecord {
+        id,
+        players: [row.get(1)?, row.get(2)?],
+        outcome,
+        elos: [row.get(6)?, row.get(7)?],
+        finished_at: row.get(8)?,
+    })
+}
+
+/// Inserts a match, returning the id it was given.
+fn insert_match(connection: &rusqlite::Connection, record: &NewMatch) -> rusqlite::Result<u64> {
+    let (outcome, winner, delta, reason) = record.outcome.to_columns();
+    connection.execute(
+        "INSERT INTO matches (
+            first_bot, second_bot, outcome, winner, score_delta,
+            first_elo, second_elo, finished_at, disqualification_reason
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            record.players[0],
+            record.players[1],
+            outcome,
+            winner,
+            delta,
+            record.elos[0],
+            record.elos[1],
+            record.finished_at,
+            reason,
+        ],
+    )?;
+    Ok(connection.last_insert_rowid() as u64)
+}
+
+/// Inserts a practice match, returning the id it was given.
+fn insert_practice_match(
+    connection: &rusqlite::Connection,
+    record: &NewPracticeMatch,
+) -> rusqlite::Result<u64> {
+    let (outcome, winner, delta, reason) = record.outcome.to_columns();
+    connection.execute(
+        "INSERT INTO practice_matches (
+            first_bot, second_bot, builtin, outcome, winner, score_delta,
+            disqualification_reason, finished_at
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            record.players[0],
+            record.players[1],
+            record.builtin,
+            outcome,
+            winner,
+            delta,
+            reason,
+            record.finished_at,
+        ],
+    )?;
+    Ok(connection.last_insert_rowid() as u64)
+}
+
+const SELECT_PRACTICE_MATCHES: &str = "
+    SELECT id, first_bot, second_bot, builtin, outcome, winner, score_delta,
+        disqualification_reason, finished_at
+    FROM practice_matches
+";
+
+fn practice_match_from_row(row: &rusqlite::Row) -> rusqlite::Result<PracticeMatchRecord> {
+    let id = row.get(0)?;
+    let outcome = MatchOutcome::from_columns(
+        &row.get::<_, String>(4)?,
+        row.get(5)?,
+        row.get(6)?,
+        row.get::<_, Option<String>>(7)?.as_deref(),
+    )
+    .ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            4,
+            rusqlite::types::Type::Text,
+            format!("practice match {id} has an invalid outcome").into(),
+        )
+    })?;
+
+    Ok(PracticeMatchRecord {
+        id,
+        players: [row.get(1)?, row.get(2)?],
+        builtin: row.get(3)?,
+        outcome,
+        finished_at: row.get(8)?,
+    })
+}
+
+fn bot_from_row(row: &rusqlite::Row) -> rusqlite::Result<BotRecord> {
+    Ok(BotRecord {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        elo: row.get(2)?,
+        password_hash: row.get(3)?,
+    })
+}
+
+/// Copies the database found at the given path to the destination using sqlite's online backup
+/// API, so it is safe to call whilst a server is using the database.
+pub fn backup(path: &Path, destination: &Path) -> Result<(), DatabaseError> {
+    // Opened without the create flag, as backing up a database that does not exist is a mistake.
+    let database = rusqlite::Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+    database.backup(DatabaseName::Main, destination, None)?;
+
+    Ok(())
+}
+
+/// Opens the database found at the given path (creating it if needed) and brings its schema up
+/// to date by applying every pending migration.
+pub fn open_database(path: &Path) -> Result<rusqlite::Connection, DatabaseError> {
+    let mut database = rusqlite::Connection::open(path)?;
+
+    let applied = migrations::run_migrations(&mut database)?;
+    if applied != 0 {
+        info!(
+            "Applied {applied} migration(s), database is now at schema version {}.",
+            migrations::latest_version()
+        );
+    }
+
+    Ok(database)
+}
+
+#[derive(Error, Debug)]
+pub enum DatabaseError {
+    #[error("rusqlite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("migration error: {0}")]
+    Migration(#[from] migrations::MigrationError),
+
+    #[error("database task failed: {0}")]
+    TaskFailed(#[from] tokio::task::JoinError),
+}
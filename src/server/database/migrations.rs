@@ -0,0 +1,164 @@
+use rusqlite::{params, Connection};
+use thiserror::Error;
+use tracing::trace;
+
+/// A single, forward-only change to the database's schema.
+pub struct Migration {
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every migration the server knows about, in the order they must be applied. The schema version
+/// of a database is the amount of migrations that were applied to it, so entries must **never**
+/// be edited, removed or reordered once released: new schema changes are always appended.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "create bots table",
+        // IF NOT EXISTS is needed as databases created before migrations existed already have this
+        // table, but no schema_version table.
+        sql: "
+            CREATE TABLE IF NOT EXISTS bots (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                password TEXT NOT NULL,
+                elo INTEGER
+            );
+        ",
+    },
+    Migration {
+        description: "create matches table",
+        // Seats are 0 for the bot that took seat 0 (which usually moves first, unless the opening
+        // says otherwise) and 1 for the other. The winner is null for ties, and the elos are each
+        // bot's elo once the match was over.
+        sql: "
+            CREATE TABLE matches (
+                id INTEGER PRIMARY KEY,
+                first_bot INTEGER NOT NULL REFERENCES bots (id),
+                second_bot INTEGER NOT NULL REFERENCES bots (id),
+                outcome TEXT NOT NULL,
+                winner INTEGER,
+                score_delta INTEGER,
+                first_elo INTEGER NOT NULL,
+                second_elo INTEGER NOT NULL,
+                finished_at INTEGER NOT NULL
+            );
+            CREATE INDEX matches_first_bot ON matches (first_bot);
+            CREATE INDEX matches_second_bot ON matches (second_bot);
+        ",
+    },
+    Migration {
+        description: "add disqualification reason to matches",
+        // Null for every outcome but disqualifications, and for disqualifications recorded before
+        // this migration.
+        sql: "ALTER TABLE matches ADD COLUMN disqualification_reason TEXT;",
+    },
+    Migration {
+        description: "create practice matches table",
+        // Same as matches, but without elos. A seat's bot is null when it was played by the
+        // built-in bot, whose name is stored alongside.
+        sql: "
+            CREATE TABLE practice_matches (
+                id INTEGER PRIMARY KEY,
+                first_bot INTEGER REFERENCES bots (id),
+                second_bot INTEGER REFERENCES bots (id),
+                builtin TEXT,
+                outcome TEXT NOT NULL,
+                winner INTEGER,
+                score_delta INTEGER,
+                disqualification_reason TEXT,
+                finished_at INTEGER NOT NULL
+            );
+            CREATE INDEX practice_matches_first_bot ON practice_matches (first_bot);
+            CREATE INDEX practice_matches_second_bot ON practice_matches (second_bot);
+        ",
+    },
+];
+
+/// The schema version a database will be at once all migrations are applied.
+#[inline]
+pub fn latest_version() -> u32 {
+    MIGRATIONS.len() as u32
+}
+
+/// Returns the schema version of the database, 0 meaning no migration was ever applied. The
+/// schema_version table is created if it does not exist yet.
+pub fn current_version(connection: &Connection) -> rusqlite::Result<u32> {
+    create_version_table(connection)?;
+
+    connection
+        .query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+            row.get::<_, Option<u32>>(0)
+        })
+        .map(|version| version.unwrap_or(0))
+}
+
+/// Applies all migrations that have not yet been applied to the database, each in its own
+/// transaction, and returns how many were applied.
+pub fn run_migrations(connection: &mut Connection) -> Result<usize, MigrationError> {
+    let current = current_version(connection)?;
+    let latest = latest_version();
+
+    // Migrations are forward-only, a database created by a newer version of the server can't be
+    // safely used by this one.
+    if current > latest {
+        return Err(MigrationError::UnknownVersion { current, latest });
+    }
+
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        let version = version as u32 + 1;
+        trace!(
+            "Applying migration {version} ({}) to the database.",
+            migration.description
+        );
+
+        let transaction = connection.transaction()?;
+        transaction
+            .execute_batch(migration.sql)
+            .map_err(|error| MigrationError::Failed {
+                version,
+                description: migration.description,
+                error,
+            })?;
+        transaction.execute(
+            "INSERT INTO schema_version (version, description, applied_at)
+                VALUES (?1, ?2, strftime('%s', 'now'))",
+            params![version, migration.description],
+        )?;
+        transaction.commit()?;
+    }
+
+    Ok((latest - current) as usize)
+}
+
+fn create_version_table(connection: &Connection) -> rusqlite::Result<()> {
+    connection.execute(
+        "
+            CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                description TEXT NOT NULL,
+                applied_at INTEGER NOT NULL
+            )
+        ",
+        [],
+    )?;
+
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("rusqlite error: {0}")]
+    DatabaseError(#[from] rusqlite::Error),
+
+    #[error("migration {version} ({description}) failed: {error}")]
+    Failed {
+        version: u32,
+        description: &'static str,
+        error: rusqlite::Error,
+    },
+
+    #[error(
+        "database is at schema version {current} but this server only knows up to version {latest}"
+    )]
+    UnknownVersion { current: u32, latest: u32 },
+}
@@ -0,0 +1,106 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rusqlite::Connection;
+use tokio::sync::Semaphore;
+
+use crate::server::metrics::metrics;
+
+use super::DatabaseError;
+
+/// Default maximum amount of connections opened to the database at once. WAL mode allows any
+/// amount of readers alongside a single writer, so this mostly bounds the amount of blocking
+/// threads used.
+pub(super) const MAX_CONNECTIONS: usize = 8;
+
+/// How long a connection waits for another one's write to finish before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A small pool of sqlite connections. Queries are run on tokio's blocking threads so that they
+/// never stall the async workers.
+pub(super) struct Pool {
+    path: PathBuf,
+    idle: Mutex<Vec<Connection>>,
+    permits: Arc<Semaphore>,
+}
+
+impl Pool {
+    /// Creates a pool of at most the given amount of connections for the database at the given
+    /// path, reusing the given (already migrated) connection as its first idle connection.
+    pub(super) fn new(
+        path: &Path,
+        connection: Connection,
+        max_connections: usize,
+    ) -> Result<Self, DatabaseError> {
+        configure(&connection)?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            idle: Mutex::new(vec![connection]),
+            permits: Arc::new(Semaphore::new(max_connections)),
+        })
+    }
+
+    /// Runs the given function with a connection from the pool on a blocking thread.
+    pub(super) async fn run<T, F>(self: &Arc<Self>, function: F) -> Result<T, DatabaseError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        // The semaphore is never closed, so acquiring a permit can't fail.
+        let wait = metrics().pool_wait.start_timer();
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the pool's semaphore should never be closed");
+        wait.observe_duration();
+
+        let pool = self.clone();
+        tokio::task::spawn_blocking(move || {
+            // The permit is moved in so it is only released once the query is done, even if the
+            // caller stopped waiting for it.
+            let _permit = permit;
+
+            let idle = pool
+                .idle
+                .lock()
+                .unwrap_or_else(|error| error.into_inner())
+                .pop();
+            let mut connection = match idle {
+                Some(connection) => connection,
+                None => {
+                    let connection = Connection::open(&pool.path)?;
+                    configure(&connection)?;
+                    connection
+                }
+            };
+
+            let result = metrics()
+                .query_latency
+                .observe_closure_duration(|| function(&mut connection));
+
+            pool.idle
+                .lock()
+                .unwrap_or_else(|error| error.into_inner())
+                .push(connection);
+
+            Ok(result?)
+        })
+        .await?
+    }
+}
+
+fn configure(connection: &Connection) -> rusqlite::Result<()> {
+    // The journal mode is stored in the database file itself, but setting it again is harmless.
+    connection
+        .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    connection.pragma_update(None, "synchronous", "NORMAL")?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+#![cfg(test)]
+
+use super::migrations::*;
+
+#[test]
+fn migrate_fresh_database() {
+    let mut connection = rusqlite::Connection::open_in_memory().unwrap();
+
+    assert_eq!(current_version(&connection).unwrap(), 0);
+    assert_eq!(run_migrations(&mut connection).unwrap(), MIGRATIONS.len());
+    assert_eq!(current_version(&connection).unwrap(), latest_version());
+}
+
+#[test]
+fn migrate_is_idempotent() {
+    let mut connection = rusqlite::Connection::open_in_memory().unwrap();
+
+    run_migrations(&mut connection).unwrap();
+    assert_eq!(run_migrations(&mut connection).unwrap(), 0);
+    assert_eq!(current_version(&connection).unwrap(), latest_version());
+}
+
+#[test]
+fn migrate_legacy_database() {
+    // Databases created before migrations existed only have the bots table.
+    let mut connection = rusqlite::Connection::open_in_memory().unwrap();
+    connection
+        .execute(
+            "CREATE TABLE bots (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                password TEXT NOT NULL,
+                elo INTEGER
+            )",
+            [],
+        )
+        .unwrap();
+    connection
+        .execute(
+            "INSERT INTO bots (name, password, elo) VALUES ('bot', 'hash', 1000)",
+            [],
+        )
+        .unwrap();
+
+    run_migrations(&mut connection).unwrap();
+
+    let count: u32 = connection
+        .query_row("SELECT COUNT(*) FROM bots", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn refuse_newer_database() {
+    let mut connection = rusqlite::Connection::open_in_memory().unwrap();
+    run_migrations(&mut connection).unwrap();
+    connection
+        .execute(
+            "INSERT INTO schema_version (version, description, applied_at) VALUES (?1, 'future', 0)",
+            [latest_version() + 1],
+        )
+        .unwrap();
+
+    assert!(matches!(
+        run_migrations(&mut connection),
+        Err(MigrationError::UnknownVersion { .. })
+    ));
+}
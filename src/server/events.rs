@@ -0,0 +1,47 @@
+use serde::Serialize;
+
+use crate::server::storage::MatchOutcome;
+
+/// Amount of events kept for subscribers that are lagging behind, past which they start
+/// missing events.
+pub const EVENT_BUFFER_SIZE: usize = 256;
+
+/// Something that happened in the lobby or on the ladder. Unlike spectator events, these are
+/// low volume and meant for dashboards.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    BotConnected {
+        name: String,
+    },
+    BotDisconnected {
+        name: String,
+    },
+    MatchStarted {
+        match_id: u64,
+        players: [String; 2],
+    },
+    MatchFinished {
+        match_id: u64,
+        players: [String; 2],
+        outcome: MatchOutcome,
+    },
+    RatingChanged {
+        name: String,
+        old_elo: u16,
+        new_elo: u16,
+    },
+}
+
+impl ServerEvent {
+    /// Name of the event, as used by server-sent events.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::BotConnected { .. } => "bot_connected",
+            Self::BotDisconnected { .. } => "bot_disconnected",
+            Self::MatchStarted { .. } => "match_started",
+            Self::MatchFinished { .. } => "match_finished",
+            Self::RatingChanged { .. } => "rating_changed",
+        }
+    }
+}
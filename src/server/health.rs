@@ -0,0 +1,92 @@
+use std::{sync::atomic::Ordering, time::Duration};
+
+use axum::{extract::State, response::IntoResponse, Json};
+use reqwest::StatusCode;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::server::{app_state::AppState, shutdown::ShutdownPhase};
+
+/// How late a round of matchmaking can be before the matchmaker is considered stuck.
+const MATCHMAKER_TOLERANCE: Duration = Duration::from_secs(10);
+
+/// Tells whether the server works: the storage can be reached and the matchmaker is running.
+pub async fn healthz(State(state): State<AppState>) -> impl IntoResponse {
+    report(checks(&state).await)
+}
+
+/// Tells whether bots should connect to the server: it must be healthy and not shutting down.
+pub async fn readyz(State(state): State<AppState>) -> impl IntoResponse {
+    let mut checks = checks(&state).await;
+    checks.accepting_bots = Check::from_failure(
+        (state.shutdown_phase() != ShutdownPhase::Running).then(|| "shutting down".to_owned()),
+    );
+
+    report(checks)
+}
+
+async fn checks(state: &AppState) -> Checks {
+    let storage = state.storage.ping().await.err().map(|error| {
+        warn!("Health check could not reach the storage: {error}");
+        error.to_string()
+    });
+
+    Checks {
+        storage: Check::from_failure(storage),
+        matchmaker: Check::from_failure(matchmaker_failure(state)),
+        accepting_bots: Check::Ok,
+    }
+}
+
+/// Whether the matchmaker ran a round recently enough.
+pub fn is_matchmaker_alive(state: &AppState) -> bool {
+    let tolerance = state.settings().matchmaking.interval + MATCHMAKER_TOLERANCE;
+
+    state
+        .last_matchmaking_round_lock()
+        .is_some_and(|last_round| last_round.elapsed() <= tolerance)
+}
+
+fn matchmaker_failure(state: &AppState) -> Option<String> {
+    if is_matchmaker_alive(state) {
+        return None;
+    }
+
+    Some(match state.matchmaker_failures.load(Ordering::Relaxed) {
+        0 => "no recent round of matchmaking".to_owned(),
+        failures => format!("restarting after failing {failures} time(s) in a row"),
+    })
+}
+
+fn report(checks: Checks) -> impl IntoResponse {
+    let healthy = [&checks.storage, &checks.matchmaker, &checks.accepting_bots]
+        .iter()
+        .all(|check| matches!(check, Check::Ok));
+    let status = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(checks))
+}
+
+#[derive(Serialize)]
+struct Checks {
+    storage: Check,
+    matchmaker: Check,
+    accepting_bots: Check,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Check {
+    Ok,
+    Failed(String),
+}
+
+impl Check {
+    fn from_failure(failure: Option<String>) -> Self {
+        failure.map_or(Self::Ok, Self::Failed)
+    }
+}
@@ -0,0 +1,109 @@
+use std::{io, net::SocketAddr, time::Duration};
+
+use axum::Router;
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use thiserror::Error;
+use tokio::{net::TcpListener, task::JoinSet};
+use tracing::info;
+
+use crate::{
+    config::ServerConfig,
+    server::{app_state::AppState, shutdown::ShutdownPhase},
+};
+
+/// How long open connections are given to close once the server has shut down. Connections
+/// that stay open forever (such as event streams) would otherwise keep the server from stopping.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A socket the server accepts connections on.
+pub struct Listener {
+    listener: std::net::TcpListener,
+    tls: Option<RustlsConfig>,
+}
+
+/// Binds every address of the configuration on the given port, loading the TLS certificate if
+/// there is one. Binding is done eagerly so that errors are reported before the server starts.
+pub async fn bind(config: &ServerConfig, port: u16) -> Result<Vec<Listener>, ListenError> {
+    let tls = match &config.tls {
+        Some(tls) => Some({
+            // Only fails if a provider was already installed, which is just as good.
+            let _ = rustls::crypto::ring::default_provider().install_default();
+
+            RustlsConfig::from_pem_file(&tls.certificate, &tls.key)
+                .await
+                .map_err(ListenError::Tls)?
+        }),
+        None => None,
+    };
+
+    let mut listeners = Vec::with_capacity(config.addresses.len());
+    for address in &config.addresses {
+        let address = SocketAddr::new(*address, port);
+        let listener = TcpListener::bind(address)
+            .await
+            .and_then(TcpListener::into_std)
+            .map_err(|source| ListenError::Bind { address, source })?;
+
+        listeners.push(Listener {
+            listener,
+            tls: tls.clone(),
+        });
+    }
+
+    Ok(listeners)
+}
+
+/// Serves the routes on every listener until the server shuts down, or one of them fails.
+pub async fn serve(listeners: Vec<Listener>, routes: Router, state: AppState) -> io::Result<()> {
+    let mut servers = JoinSet::new();
+
+    for Listener { listener, tls } in listeners {
+        let address = listener.local_addr()?;
+        let service = routes
+            .clone()
+            .into_make_service_with_connect_info::<SocketAddr>();
+
+        // The server keeps running whilst the rest of the server shuts down, so that bots can
+        // finish their matches (and spectators follow them), and only stops once everything
+        // else is over.
+        let handle = Handle::new();
+        let shutdown_handle = handle.clone();
+        let shutdown_state = state.clone();
+        servers.spawn(async move {
+            shutdown_state.reached(ShutdownPhase::Stopped).await;
+            shutdown_handle.graceful_shutdown(Some(CLOSE_TIMEOUT));
+            Ok(())
+        });
+
+        match tls {
+            Some(tls) => {
+                info!("Listening on https://{address}");
+                let server = axum_server::from_tcp_rustls(listener, tls).handle(handle);
+                servers.spawn(server.serve(service));
+            }
+            None => {
+                info!("Listening on http://{address}");
+                let server = axum_server::from_tcp(listener).handle(handle);
+                servers.spawn(server.serve(service));
+            }
+        }
+    }
+
+    while let Some(result) = servers.join_next().await {
+        result??;
+    }
+
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum ListenError {
+    #[error("could not bind the server to address {address}: {source}")]
+    Bind {
+        address: SocketAddr,
+        source: io::Error,
+    },
+
+    #[error("could not load the TLS certificate: {0}")]
+    Tls(io::Error),
+}
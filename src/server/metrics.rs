@@ -0,0 +1,166 @@
+use std::sync::LazyLock;
+
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter,
+    IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use reqwest::StatusCode;
+use tracing::error;
+
+use crate::server::app_state::AppState;
+
+/// Every metric exposed by the server. They are global as some of them are recorded deep
+/// within code that knows nothing about the app state (such as the database pool).
+pub struct Metrics {
+    registry: Registry,
+
+    pub connected_bots: IntGauge,
+    pub pending_bots: IntGauge,
+    pub running_matches: IntGauge,
+    /// Incremented at each round of matchmaking, so a stuck matchmaker shows up as a flat line.
+    pub matchmaking_rounds: IntCounter,
+    pub matches_started: IntCounter,
+    /// Labelled by outcome kind (tie, won, disqualified or aborted).
+    pub matches_finished: IntCounterVec,
+    /// Times the matchmaker stopped unexpectedly and had to be restarted.
+    pub matchmaker_restarts: IntCounter,
+    /// Matches whose task panicked, which are recorded as aborted.
+    pub match_panics: IntCounter,
+    /// Time each bot took to play its moves, labelled by bot name.
+    pub move_latency: HistogramVec,
+    /// Labelled by the reason of the disqualification.
+    pub disqualifications: IntCounterVec,
+    /// Time taken by database queries, once they got a connection.
+    pub query_latency: Histogram,
+    /// Time spent waiting for a connection from the database pool.
+    pub pool_wait: Histogram,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Returns the server's metrics.
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("mancala".to_owned()), None)
+            .expect("the metrics prefix should be valid");
+
+        // Queries and pool waits are expected to take from a fraction of a millisecond to a few
+        // seconds (the busy timeout), and moves up to the move timeout.
+        let database_buckets = exponential_buckets(0.0001, 4.0, 10).expect("valid buckets");
+        let move_buckets = exponential_buckets(0.001, 4.0, 9).expect("valid buckets");
+
+        let metrics = Self {
+            connected_bots: IntGauge::new("connected_bots", "Bots on the ladder")
+                .expect("valid metric"),
+            pending_bots: IntGauge::new("pending_bots", "Bots waiting for the matchmaker")
+                .expect("valid metric"),
+            running_matches: IntGauge::new("running_matches", "Matches being played")
+                .expect("valid metric"),
+            matchmaking_rounds: IntCounter::new(
+                "matchmaking_rounds_total",
+                "Rounds of matchmaking run",
+            )
+            .expect("valid metric"),
+            matches_started: IntCounter::new("matches_started_total", "Matches started")
+                .expect("valid metric"),
+            matches_finished: IntCounterVec::new(
+                Opts::new("matches_finished_total", "Matches finished, by outcome"),
+                &["outcome"],
+            )
+            .expect("valid metric"),
+            matchmaker_restarts: IntCounter::new(
+                "matchmaker_restarts_total",
+                "Times the matchmaker was restarted after failing",
+            )
+            .expect("valid metric"),
+            match_panics: IntCounter::new("match_panics_total", "Matches that panicked")
+                .expect("valid metric"),
+            move_latency: HistogramVec::new(
+                HistogramOpts::new("move_latency_seconds", "Time bots take to move, by bot")
+                    .buckets(move_buckets),
+                &["bot"],
+            )
+            .expect("valid metric"),
+            disqualifications: IntCounterVec::new(
+                Opts::new("disqualifications_total", "Disqualifications, by reason"),
+                &["reason"],
+            )
+            .expect("valid metric"),
+            query_latency: Histogram::with_opts(
+                HistogramOpts::new("database_query_seconds", "Time taken by database queries")
+                    .buckets(database_buckets.clone()),
+            )
+            .expect("valid metric"),
+            pool_wait: Histogram::with_opts(
+                HistogramOpts::new(
+                    "database_pool_wait_seconds",
+                    "Time spent waiting for a database connection",
+                )
+                .buckets(database_buckets),
+            )
+            .expect("valid metric"),
+            registry,
+        };
+
+        let collectors: [Box<dyn prometheus::core::Collector>; 12] = [
+            Box::new(metrics.connected_bots.clone()),
+            Box::new(metrics.pending_bots.clone()),
+            Box::new(metrics.running_matches.clone()),
+            Box::new(metrics.matchmaking_rounds.clone()),
+            Box::new(metrics.matches_started.clone()),
+            Box::new(metrics.matches_finished.clone()),
+            Box::new(metrics.matchmaker_restarts.clone()),
+            Box::new(metrics.match_panics.clone()),
+            Box::new(metrics.move_latency.clone()),
+            Box::new(metrics.disqualifications.clone()),
+            Box::new(metrics.query_latency.clone()),
+            Box::new(metrics.pool_wait.clone()),
+        ];
+        for collector in collectors {
+            metrics
+                .registry
+                .register(collector)
+                .expect("metrics should only be registered once");
+        }
+
+        metrics
+    }
+}
+
+/// Exposes the metrics in Prometheus' text format.
+pub async fn serve_metrics(State(state): State<AppState>) -> Response {
+    let metrics = metrics();
+
+    // Gauges are only updated when scraped, as they can be read straight from the state.
+    metrics
+        .connected_bots
+        .set(state.connected_bots.lock().await.len() as i64);
+    metrics
+        .pending_bots
+        .set(state.pending_bots.lock().await.len() as i64);
+    metrics
+        .running_matches
+        .set(state.running_matches_lock().len() as i64);
+
+    let mut buffer = Vec::new();
+    let encoder = TextEncoder::new();
+    if let Err(error) = encoder.encode(&metrics.registry.gather(), &mut buffer) {
+        error!("Could not encode the metrics: {error}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    (
+        [(header::CONTENT_TYPE, encoder.format_type().to_owned())],
+        buffer,
+    )
+        .into_response()
+}
@@ -1,2 +1,13 @@
 pub mod api;
 pub mod app_state;
+pub mod bot_socket;
+pub mod database;
+pub mod events;
+pub mod health;
+pub mod listen;
+pub mod metrics;
+pub mod presence;
+pub mod shutdown;
+pub mod spectators;
+pub mod storage;
+mod tests;
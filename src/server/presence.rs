@@ -0,0 +1,121 @@
+use axum::extract::ws::{close_code, CloseFrame};
+use tokio::{task::JoinSet, time::Instant};
+use tracing::{trace, warn};
+
+use crate::server::{
+    app_state::{AppState, Bot, DisconnectedBot},
+    events::ServerEvent,
+};
+
+/// Periodically pings every logged in bot, evicting the ones that don't answer. Pongs are noticed
+/// by the task driving each bot's web socket, so bots are pinged even whilst being queried.
+pub async fn run_heartbeats(state: AppState) {
+    trace!("Started the heartbeat task.");
+
+    loop {
+        tokio::time::sleep(state.settings().presence.heartbeat_interval).await;
+
+        // Bots that did not come back in time are forgotten for good.
+        let now = Instant::now();
+        state
+            .disconnected_bots
+            .lock()
+            .await
+            .retain(|id, disconnected| {
+                let keep = disconnected.deadline > now;
+                if !keep {
+                    trace!(
+                        "Bot {} did not resume its session in time.",
+                        disconnected.bot.name
+                    );
+                    state.presence_lock().remove(id);
+                }
+                keep
+            });
+
+        let mut bots: Vec<_> = state.pending_bots.lock().await.clone();
+        bots.extend(state.connected_bots.lock().await.iter().cloned());
+
+        // Each bot is pinged concurrently, so that slow bots don't delay the others.
+        let mut pings = JoinSet::new();
+        for bot in bots {
+            pings.spawn(ping(state.clone(), bot));
+        }
+        pings.join_all().await;
+    }
+}
+
+async fn ping(state: AppState, bot: Bot) {
+    let Some(socket) = bot.socket.clone() else {
+        return;
+    };
+
+    let pong_timeout = state.settings().presence.pong_timeout;
+    if socket.ping(pong_timeout).await {
+        state.mark_seen(bot.id);
+    } else {
+        warn!("Bot {} did not answer its ping, evicting it.", bot.name);
+        evict(&state, &bot).await;
+    }
+}
+
+/// Removes the bot from the server and closes its socket. The bot then has a grace period to
+/// resume its session, after which its running matches end with its disqualification.
+pub async fn evict(state: &AppState, bot: &Bot) {
+    let was_pending = {
+        let mut pending_bots = state.pending_bots.lock().await;
+        let count = pending_bots.len();
+        pending_bots.retain(|pending_bot| pending_bot != bot);
+        pending_bots.len() != count
+    };
+    let was_connected = state.connected_bots.lock().await.remove(bot);
+
+    if let Some(socket) = &bot.socket {
+        // The bot is most likely already gone, so failing to say goodbye is expected.
+        socket.close(None);
+    }
+
+    if was_pending || was_connected {
+        state.disconnected_bots.lock().await.insert(
+            bot.id,
+            DisconnectedBot {
+                bot: bot.clone(),
+                was_connected,
+                deadline: Instant::now() + state.settings().matches.reconnection_grace,
+            },
+        );
+        state.publish(ServerEvent::BotDisconnected {
+            name: bot.name.to_string(),
+        });
+    }
+}
+
+/// Removes the bot from the server for good and closes its socket. Unlike [`evict`], the bot can't
+/// resume its session, and has to log in again.
+pub async fn kick(state: &AppState, bot: &Bot, reason: &str) {
+    let was_pending = {
+        let mut pending_bots = state.pending_bots.lock().await;
+        let count = pending_bots.len();
+        pending_bots.retain(|pending_bot| pending_bot != bot);
+        pending_bots.len() != count
+    };
+    let was_connected = state.connected_bots.lock().await.remove(bot);
+    state.disconnected_bots.lock().await.remove(&bot.id);
+    state.presence_lock().remove(&bot.id);
+
+    if let Some(socket) = &bot.socket {
+        // The bot may already be gone, which is fine.
+        socket.close(Some(CloseFrame {
+            code: close_code::POLICY,
+            reason: reason.into(),
+        }));
+    }
+
+    // Bots that were already disconnected were announced as such when they got evicted.
+    if was_pending || was_connected {
+        state.publish(ServerEvent::BotDisconnected {
+            name: bot.name.to_string(),
+        });
+    }
+    trace!("Kicked out bot {}: {reason}", bot.name);
+}
@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use axum::extract::ws::{close_code, CloseFrame};
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+use crate::server::app_state::AppState;
+
+/// How long aborted matches are given to record their result.
+const ABORT_DEADLINE: Duration = Duration::from_secs(5);
+
+/// Time between two checks of whether all matches are over.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where the server is in its shutdown sequence, see [`shut_down`]. Phases only ever go
+/// forward.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ShutdownPhase {
+    /// The server runs normally.
+    Running,
+    /// Logins are refused and no new match is started, running matches are left to finish.
+    Draining,
+    /// Running matches are stopped and recorded as aborted.
+    Aborting,
+    /// Everything is over, the web server can stop.
+    Stopped,
+}
+
+/// Resolves once the process is asked to stop, either with Ctrl-C or (on unix) SIGTERM.
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(error) = tokio::signal::ctrl_c().await {
+            error!("Could not listen for Ctrl-C: {error}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(error) => {
+                error!("Could not listen for SIGTERM: {error}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Shuts the server down: stops accepting logins, lets running matches finish until the
+/// drain deadline (aborting the remaining ones), says goodbye to every bot and flushes the
+/// storage. The web server is told to stop once this returns.
+pub async fn shut_down(state: &AppState) {
+    state.enter_phase(ShutdownPhase::Draining);
+    let running = state.running_matches_lock().len();
+    info!("Shutting down, waiting for {running} running matches to finish.");
+
+    let deadline = state.settings().shutdown.drain_deadline;
+    if !wait_for_matches(state, deadline).await {
+        let running = state.running_matches_lock().len();
+        warn!("{running} matches did not finish in time, aborting them.");
+
+        state.enter_phase(ShutdownPhase::Aborting);
+        if !wait_for_matches(state, ABORT_DEADLINE).await {
+            error!("Some aborted matches could not be recorded.");
+        }
+    }
+
+    // Every match is over, so nothing else is using the sockets.
+    let mut bots: Vec<_> = state.pending_bots.lock().await.drain(..).collect();
+    bots.extend(state.connected_bots.lock().await.drain());
+    for bot in bots {
+        let Some(socket) = bot.socket else {
+            continue;
+        };
+
+        socket.close(Some(CloseFrame {
+            code: close_code::AWAY,
+            reason: "server shutting down".into(),
+        }));
+    }
+
+    if let Err(error) = state.storage.flush().await {
+        error!("Could not flush the storage: {error}");
+    }
+
+    state.enter_phase(ShutdownPhase::Stopped);
+    info!("Shut down.");
+}
+
+/// Waits for every running match to be over, returning false if they are not by the deadline.
+async fn wait_for_matches(state: &AppState, deadline: Duration) -> bool {
+    let deadline = Instant::now() + deadline;
+
+    while !state.running_matches_lock().is_empty() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    true
+}
@@ -0,0 +1,48 @@
+use serde::Serialize;
+
+use crate::{mancala::Game, server::storage::MatchOutcome};
+
+/// Amount of events kept for spectators that are lagging behind. Spectators that fall further
+/// behind than this miss events rather than slowing down matches.
+pub const SPECTATOR_BUFFER_SIZE: usize = 1024;
+
+/// Something that happened in a running match, as sent to spectators. Match ids are the ids
+/// given to running matches, which are not the ids matches are stored with.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SpectatorEvent {
+    Started {
+        match_id: u64,
+        players: [String; 2],
+        game: Game,
+    },
+    Moved {
+        match_id: u64,
+        /// Seat of the player that moved, as an index into the match's players (which is not
+        /// necessarily the player that moved first, depending on the opening).
+        player: u8,
+        cell: u8,
+        game: Game,
+    },
+    /// The player moving second traded sides with its opponent, as allowed by the pie rule.
+    Swapped {
+        match_id: u64,
+        player: u8,
+        game: Game,
+    },
+    Finished {
+        match_id: u64,
+        outcome: MatchOutcome,
+    },
+}
+
+impl SpectatorEvent {
+    pub fn match_id(&self) -> u64 {
+        match self {
+            Self::Started { match_id, .. }
+            | Self::Moved { match_id, .. }
+            | Self::Swapped { match_id, .. }
+            | Self::Finished { match_id, .. } => *match_id,
+        }
+    }
+}
@@ -0,0 +1,367 @@
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{mancala::play_match::DisqualificationReason, server::database::DatabaseError};
+
+pub mod memory;
+mod tests;
+pub mod transfer;
+
+/// Everything the server needs to persist: bots, their ratings and the history of their matches.
+/// The server only ever goes through this trait, so the backend can be swapped freely (sqlite for
+/// real deployments, in memory for tests and ephemeral servers).
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Fetches the bot with the given name, if it exists.
+    async fn find_bot(&self, name: &str) -> Result<Option<BotRecord>, StorageError>;
+
+    /// Returns every bot, from highest to lowest elo.
+    async fn bots(&self) -> Result<Vec<BotRecord>, StorageError>;
+
+    /// Returns every bot for an export, the given elo standing in for the ones that have none
+    /// (which databases created before migrations existed allow). Unlike [`Storage::bots`], bots
+    /// that can't be read are an error rather than left out, so that none goes missing from a
+    /// snapshot. Backends that store every bot with an elo can rely on the default
+    /// implementation.
+    async fn export_bots(&self, _default_elo: u16) -> Result<Vec<BotRecord>, StorageError> {
+        self.bots().await
+    }
+
+    /// Inserts a new bot, returning false (and storing nothing) if the name is already taken.
+    async fn insert_bot(
+        &self,
+        name: &str,
+        password_hash: &str,
+        elo: u16,
+    ) -> Result<bool, StorageError>;
+
+    /// Stores the bots, matches and practice matches of an import all at once: should one of them
+    /// fail to be stored (a bot's name being taken for instance), none is. The matches refer to
+    /// the bots by the ids given in `bots`, which are replaced by the ones the bots are given.
+    async fn import(
+        &self,
+        bots: Vec<BotRecord>,
+        matches: Vec<NewMatch>,
+        practice_matches: Vec<NewPracticeMatch>,
+    ) -> Result<(), StorageError>;
+
+    /// Sets the elo of the bot with the given id, returning false if no such bot exists.
+    async fn update_elo(&self, id: u16, elo: u16) -> Result<bool, StorageError>;
+
+    /// Stores the result of a finished match, returning the id it was given.
+    async fn record_match(&self, record: NewMatch) -> Result<u64, StorageError>;
+
+    /// Returns every stored match, from oldest to newest.
+    async fn matches(&self) -> Result<Vec<MatchRecord>, StorageError>;
+
+    /// Returns every match the bot with the given id took part in, from oldest to newest.
+    async fn matches_of(&self, bot_id: u16) -> Result<Vec<MatchRecord>, StorageError>;
+
+    /// Stores the result of a finished practice match, returning the id it was given.
+    async fn record_practice_match(&self, record: NewPracticeMatch) -> Result<u64, StorageError>;
+
+    /// Returns every stored practice match, from oldest to newest.
+    async fn practice_matches(&self) -> Result<Vec<PracticeMatchRecord>, StorageError>;
+
+    /// Returns every practice match the bot with the given id took part in, from oldest to
+    /// newest.
+    async fn practice_matches_of(
+        &self,
+        bot_id: u16,
+    ) -> Result<Vec<PracticeMatchRecord>, StorageError>;
+
+    /// Returns the statistics of every bot that played at least one match, by bot id. Aborted
+    /// matches are not counted. The default implementation goes through the whole match
+    /// history, so backends that can aggregate it more efficiently should override it.
+    async fn bot_stats(&self) -> Result<HashMap<u16, BotStats>, StorageError> {
+        let mut accumulators: HashMap<u16, StatsAccumulator> = HashMap::new();
+
+        for record in self.matches().await? {
+            if record.outcome == MatchOutcome::Aborted {
+                continue;
+            }
+
+            for seat in 0..2 {
+                let accumulator = accumulators.entry(record.players[seat]).or_default();
+                accumulator.add(record.outcome.result_for(seat as u8), record.elos[seat]);
+            }
+        }
+
+        Ok(accumulators
+            .into_iter()
+            .map(|(id, accumulator)| (id, accumulator.finish()))
+            .collect())
+    }
+
+    /// Checks that the storage can be reached. Always succeeds by default.
+    async fn ping(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    /// Makes sure everything written so far is durably stored, which is done before the server
+    /// shuts down. Does nothing by default.
+    async fn flush(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+/// A bot as it is stored.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BotRecord {
+    pub id: u16,
+    pub name: String,
+    pub elo: u16,
+    pub password_hash: String,
+}
+
+/// How a stored match ended. Seats are 0 for the match's first player, which moves first unless
+/// the opening says otherwise, and 1 for the other.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MatchOutcome {
+    Tie,
+    Won {
+        winner: u8,
+        delta: u8,
+    },
+    Disqualified {
+        loser: u8,
+        /// Unknown for matches recorded before reasons were stored.
+        #[serde(default)]
+        reason: Option<DisqualificationReason>,
+    },
+    /// The match was cut short (e.g. by the server shutting down), so nobody won.
+    Aborted,
+}
+
+impl MatchOutcome {
+    /// Flattens the outcome into the (kind, winner's seat, score delta, disqualification reason)
+    /// columns it is stored as.
+    pub fn to_columns(self) -> (&'static str, Option<u8>, Option<u8>, Option<&'static str>) {
+        match self {
+            Self::Tie => ("tie", None, None, None),
+            Self::Won { winner, delta } => ("won", Some(winner), Some(delta), None),
+            Self::Disqualified { loser, reason } => (
+                "disqualified",
+                Some(1 - loser),
+                None,
+                reason.map(DisqualificationReason::name),
+            ),
+            Self::Aborted => ("aborted", None, None, None),
+        }
+    }
+
+    /// Whether the seats the outcome refers to exist, which outcomes read from an untrusted source
+    /// (such as an imported snapshot) must be checked for before being stored.
+    pub fn is_valid(self) -> bool {
+        match self {
+            Self::Won { winner: seat, .. } | Self::Disqualified { loser: seat, .. } => seat < 2,
+            Self::Tie | Self::Aborted => true,
+        }
+    }
+
+    /// Inverse of [`MatchOutcome::to_columns`], returning None if the columns are inconsistent.
+    pub fn from_columns(
+        kind: &str,
+        winner: Option<u8>,
+        delta: Option<u8>,
+        reason: Option<&str>,
+    ) -> Option<Self> {
+        match (kind, winner, delta) {
+            ("tie", _, _) => Some(Self::Tie),
+            ("won", Some(winner @ 0..2), Some(delta)) => Some(Self::Won { winner, delta }),
+            ("disqualified", Some(winner @ 0..2), _) => Some(Self::Disqualified {
+                loser: 1 - winner,
+                reason: match reason {
+                    Some(reason) => Some(DisqualificationReason::from_name(reason)?),
+                    None => None,
+                },
+            }),
+            ("aborted", _, _) => Some(Self::Aborted),
+            _ => None,
+        }
+    }
+}
+
+/// How a match ended from the point of view of one of its players.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchResult {
+    Win,
+    Loss,
+    Tie,
+    Aborted,
+}
+
+impl MatchOutcome {
+    /// Returns how the match ended for the player in the given seat.
+    pub fn result_for(self, seat: u8) -> MatchResult {
+        match self {
+            Self::Tie => MatchResult::Tie,
+            Self::Aborted => MatchResult::Aborted,
+            Self::Won { winner, .. } if winner == seat => MatchResult::Win,
+            Self::Disqualified { loser, .. } if loser != seat => MatchResult::Win,
+            _ => MatchResult::Loss,
+        }
+    }
+}
+
+/// Aggregated results of a bot over all of its matches.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct BotStats {
+    pub games: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub ties: u32,
+    /// Standard deviation of the bot's elo over its matches. A high deviation means the bot's
+    /// rating hasn't settled yet (or that its strength varies a lot).
+    pub rating_deviation: f64,
+}
+
+/// Accumulates match results into [`BotStats`].
+#[derive(Default)]
+pub(crate) struct StatsAccumulator {
+    stats: BotStats,
+    elo_sum: f64,
+    elo_squared_sum: f64,
+}
+
+impl StatsAccumulator {
+    /// Adds the result of a match, unless it was aborted.
+    pub(crate) fn add(&mut self, result: MatchResult, elo: u16) {
+        match result {
+            MatchResult::Win => self.stats.wins += 1,
+            MatchResult::Loss => self.stats.losses += 1,
+            MatchResult::Tie => self.stats.ties += 1,
+            MatchResult::Aborted => return,
+        }
+        self.stats.games += 1;
+
+        self.elo_sum += elo as f64;
+        self.elo_squared_sum += (elo as f64).powi(2);
+    }
+
+    pub(crate) fn finish(mut self) -> BotStats {
+        self.stats.rating_deviation =
+            deviation(self.stats.games, self.elo_sum, self.elo_squared_sum);
+        self.stats
+    }
+}
+
+/// Standard deviation of a set of values, given their count, sum and sum of squares.
+pub(crate) fn deviation(count: u32, sum: f64, squared_sum: f64) -> f64 {
+    if count == 0 {
+        return 0.0;
+    }
+
+    let mean = sum / count as f64;
+    // Rounding errors can make the variance slightly negative when all values are equal.
+    (squared_sum / count as f64 - mean.powi(2)).max(0.0).sqrt()
+}
+
+/// A match that is yet to be stored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewMatch {
+    /// Ids of the bots, in seat order.
+    pub players: [u16; 2],
+    pub outcome: MatchOutcome,
+    /// Elo of each bot once the match was over, in seat order.
+    pub elos: [u16; 2],
+    /// Unix timestamp (in seconds) of the end of the match.
+    pub finished_at: u64,
+}
+
+impl NewMatch {
+    /// Creates a match record that finished right now.
+    pub fn now(players: [u16; 2], outcome: MatchOutcome, elos: [u16; 2]) -> Self {
+        Self {
+            players,
+            outcome,
+            elos,
+            finished_at: unix_timestamp(),
+        }
+    }
+}
+
+/// A stored match.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MatchRecord {
+    pub id: u64,
+    pub players: [u16; 2],
+    pub outcome: MatchOutcome,
+    pub elos: [u16; 2],
+    pub finished_at: u64,
+}
+
+impl MatchRecord {
+    pub(crate) fn from_new(id: u64, record: NewMatch) -> Self {
+        Self {
+            id,
+            players: record.players,
+            outcome: record.outcome,
+            elos: record.elos,
+            finished_at: record.finished_at,
+        }
+    }
+}
+
+/// A practice match that is yet to be stored. Practice matches don't count towards the ladder, so
+/// they are kept apart from rated ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewPracticeMatch {
+    /// Ids of the bots, in seat order, None standing for the built-in bot if there is one.
+    pub players: [Option<u16>; 2],
+    /// Name of the built-in bot, if one of the players is.
+    pub builtin: Option<String>,
+    pub outcome: MatchOutcome,
+    /// Unix timestamp (in seconds) of the end of the match.
+    pub finished_at: u64,
+}
+
+/// A stored practice match.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PracticeMatchRecord {
+    pub id: u64,
+    pub players: [Option<u16>; 2],
+    pub builtin: Option<String>,
+    pub outcome: MatchOutcome,
+    pub finished_at: u64,
+}
+
+impl PracticeMatchRecord {
+    pub(crate) fn from_new(id: u64, record: NewPracticeMatch) -> Self {
+        Self {
+            id,
+            players: record.players,
+            builtin: record.builtin,
+            outcome: record.outcome,
+            finished_at: record.finished_at,
+        }
+    }
+}
+
+/// Current unix timestamp, in seconds.
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("database error: {0}")]
+    Database(#[from] DatabaseError),
+
+    #[error("corrupted record: {0}")]
+    Corrupted(String),
+
+    #[error("a bot named {0} already exists")]
+    NameInUse(String),
+}
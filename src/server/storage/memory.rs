@@ -0,0 +1,154 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use super::{
+    BotRecord, MatchRecord, NewMatch, NewPracticeMatch, PracticeMatchRecord, Storage, StorageError,
+};
+
+/// Storage that only lives as long as the server does. Useful for tests and for ephemeral
+/// development servers that shouldn't leave a database file behind.
+#[derive(Default)]
+pub struct MemoryStorage {
+    data: Mutex<MemoryData>,
+}
+
+#[derive(Default)]
+struct MemoryData {
+    bots: Vec<BotRecord>,
+    matches: Vec<MatchRecord>,
+    practice_matches: Vec<PracticeMatchRecord>,
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn find_bot(&self, name: &str) -> Result<Option<BotRecord>, StorageError> {
+        let data = self.data.lock().await;
+        Ok(data.bots.iter().find(|bot| bot.name == name).cloned())
+    }
+
+    async fn bots(&self) -> Result<Vec<BotRecord>, StorageError> {
+        let mut bots = self.data.lock().await.bots.clone();
+        bots.sort_by_key(|bot| Reverse(bot.elo));
+        Ok(bots)
+    }
+
+    async fn insert_bot(
+        &self,
+        name: &str,
+        password_hash: &str,
+        elo: u16,
+    ) -> Result<bool, StorageError> {
+        let mut data = self.data.lock().await;
+        if data.bots.iter().any(|bot| bot.name == name) {
+            return Ok(false);
+        }
+
+        // Ids start at 1, like sqlite's.
+        let id = data.bots.iter().map(|bot| bot.id).max().unwrap_or(0) + 1;
+        data.bots.push(BotRecord {
+            id,
+            name: name.to_owned(),
+            elo,
+            password_hash: password_hash.to_owned(),
+        });
+
+        Ok(true)
+    }
+
+    async fn import(
+        &self,
+        bots: Vec<BotRecord>,
+        matches: Vec<NewMatch>,
+        practice_matches: Vec<NewPracticeMatch>,
+    ) -> Result<(), StorageError> {
+        // Everything is stored under the same lock, after checking that it can be.
+        let mut data = self.data.lock().await;
+        if let Some(bot) = bots
+            .iter()
+            .find(|bot| data.bots.iter().any(|other| other.name == bot.name))
+        {
+            return Err(StorageError::NameInUse(bot.name.clone()));
+        }
+
+        let mut ids = HashMap::new();
+        for bot in bots {
+            let id = data.bots.iter().map(|bot| bot.id).max().unwrap_or(0) + 1;
+            ids.insert(bot.id, id);
+            data.bots.push(BotRecord { id, ..bot });
+        }
+
+        for mut record in matches {
+            record.players = record.players.map(|id| ids[&id]);
+            let id = data.matches.len() as u64 + 1;
+            data.matches.push(MatchRecord::from_new(id, record));
+        }
+        for mut record in practice_matches {
+            record.players = record.players.map(|id| id.map(|id| ids[&id]));
+            let id = data.practice_matches.len() as u64 + 1;
+            data.practice_matches
+                .push(PracticeMatchRecord::from_new(id, record));
+        }
+
+        Ok(())
+    }
+
+    async fn update_elo(&self, id: u16, elo: u16) -> Result<bool, StorageError> {
+        let mut data = self.data.lock().await;
+        let Some(bot) = data.bots.iter_mut().find(|bot| bot.id == id) else {
+            return Ok(false);
+        };
+
+        bot.elo = elo;
+        Ok(true)
+    }
+
+    async fn record_match(&self, record: NewMatch) -> Result<u64, StorageError> {
+        let mut data = self.data.lock().await;
+        let id = data.matches.len() as u64 + 1;
+        data.matches.push(MatchRecord::from_new(id, record));
+
+        Ok(id)
+    }
+
+    async fn matches(&self) -> Result<Vec<MatchRecord>, StorageError> {
+        Ok(self.data.lock().await.matches.clone())
+    }
+
+    async fn matches_of(&self, bot_id: u16) -> Result<Vec<MatchRecord>, StorageError> {
+        let data = self.data.lock().await;
+        Ok(data
+            .matches
+            .iter()
+            .filter(|record| record.players.contains(&bot_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn record_practice_match(&self, record: NewPracticeMatch) -> Result<u64, StorageError> {
+        let mut data = self.data.lock().await;
+        let id = data.practice_matches.len() as u64 + 1;
+        data.practice_matches
+            .push(PracticeMatchRecord::from_new(id, record));
+
+        Ok(id)
+    }
+
+    async fn practice_matches(&self) -> Result<Vec<PracticeMatchRecord>, StorageError> {
+        Ok(self.data.lock().await.practice_matches.clone())
+    }
+
+    async fn practice_matches_of(
+        &self,
+        bot_id: u16,
+    ) -> Result<Vec<PracticeMatchRecord>, StorageError> {
+        let data = self.data.lock().await;
+        Ok(data
+            .practice_matches
+            .iter()
+            .filter(|record| record.players.contains(&Some(bot_id)))
+            .cloned()
+            .collect())
+    }
+}
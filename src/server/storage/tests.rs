@@ -0,0 +1,332 @@
+#![cfg(test)]
+
+use super::{memory::MemoryStorage, *};
+use crate::server::database::Database;
+
+fn temporary_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("match-server-{name}-{}.db", std::process::id()))
+}
+
+/// Opens a fresh database in the temporary directory, removing any leftover from previous runs.
+fn temporary_database(name: &str) -> Database {
+    let path = temporary_path(name);
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+
+    Database::open(&path).unwrap()
+}
+
+async fn insert_and_find_bots(storage: &impl Storage) {
+    assert!(storage.insert_bot("a", "hash", 1000).await.unwrap());
+    assert!(storage.insert_bot("b", "hash", 1000).await.unwrap());
+    assert!(!storage.insert_bot("a", "other", 1000).await.unwrap());
+
+    let a = storage.find_bot("a").await.unwrap().unwrap();
+    assert_eq!(a.password_hash, "hash");
+    assert!(storage.update_elo(a.id, 1010).await.unwrap());
+    assert!(!storage.update_elo(u16::MAX, 1010).await.unwrap());
+
+    let names: Vec<_> = storage
+        .bots()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|bot| (bot.name, bot.elo))
+        .collect();
+    assert_eq!(names, [("a".to_owned(), 1010), ("b".to_owned(), 1000)]);
+
+    assert!(storage.find_bot("missing").await.unwrap().is_none());
+}
+
+async fn record_matches(storage: &impl Storage) {
+    let mut ids = Vec::new();
+    for name in ["a", "b", "c"] {
+        storage.insert_bot(name, "hash", 1000).await.unwrap();
+        ids.push(storage.find_bot(name).await.unwrap().unwrap().id);
+    }
+
+    let outcomes = [
+        MatchOutcome::Won {
+            winner: 1,
+            delta: 4,
+        },
+        MatchOutcome::Tie,
+        MatchOutcome::Disqualified {
+            loser: 0,
+            reason: Some(DisqualificationReason::IllegalMove),
+        },
+        // Aborted matches are stored, but not counted in the statistics.
+        MatchOutcome::Aborted,
+    ];
+    for outcome in outcomes {
+        storage
+            .record_match(NewMatch::now([ids[0], ids[1]], outcome, [996, 1004]))
+            .await
+            .unwrap();
+    }
+    storage
+        .record_match(NewMatch::now(
+            [ids[1], ids[2]],
+            MatchOutcome::Tie,
+            [1004, 1000],
+        ))
+        .await
+        .unwrap();
+
+    let matches = storage.matches_of(ids[0]).await.unwrap();
+    assert_eq!(
+        matches
+            .iter()
+            .map(|record| record.outcome)
+            .collect::<Vec<_>>(),
+        outcomes
+    );
+    assert_eq!(storage.matches_of(ids[1]).await.unwrap().len(), 5);
+    assert_eq!(storage.matches_of(ids[2]).await.unwrap().len(), 1);
+
+    let stats = storage.bot_stats().await.unwrap();
+    let summary = |id| {
+        let stats: &BotStats = &stats[&id];
+        [stats.games, stats.wins, stats.losses, stats.ties]
+    };
+    assert_eq!(summary(ids[0]), [3, 0, 2, 1]);
+    assert_eq!(summary(ids[1]), [4, 2, 0, 2]);
+    assert_eq!(summary(ids[2]), [1, 0, 0, 1]);
+    assert_eq!(stats[&ids[0]].rating_deviation, 0.0);
+}
+
+async fn record_practice_matches(storage: &impl Storage) {
+    storage.insert_bot("a", "hash", 1000).await.unwrap();
+    let a = storage.find_bot("a").await.unwrap().unwrap();
+
+    let record = NewPracticeMatch {
+        players: [None, Some(a.id)],
+        builtin: Some("greedy".to_owned()),
+        outcome: MatchOutcome::Won {
+            winner: 1,
+            delta: 6,
+        },
+        finished_at: 42,
+    };
+    let id = storage.record_practice_match(record.clone()).await.unwrap();
+
+    let expected = [PracticeMatchRecord::from_new(id, record)];
+    assert_eq!(storage.practice_matches_of(a.id).await.unwrap(), expected);
+    assert_eq!(storage.practice_matches().await.unwrap(), expected);
+    // Practice matches stay out of the ladder.
+    assert!(storage.matches_of(a.id).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn memory_insert_and_find_bots() {
+    insert_and_find_bots(&MemoryStorage::default()).await;
+}
+
+#[tokio::test]
+async fn database_insert_and_find_bots() {
+    insert_and_find_bots(&temporary_database("insert-and-find-bots")).await;
+}
+
+#[tokio::test]
+async fn memory_record_matches() {
+    record_matches(&MemoryStorage::default()).await;
+}
+
+#[tokio::test]
+async fn database_record_matches() {
+    record_matches(&temporary_database("record-matches")).await;
+}
+
+#[tokio::test]
+async fn memory_record_practice_matches() {
+    record_practice_matches(&MemoryStorage::default()).await;
+}
+
+#[tokio::test]
+async fn database_record_practice_matches() {
+    record_practice_matches(&temporary_database("record-practice-matches")).await;
+}
+
+#[tokio::test]
+async fn export_and_import() {
+    let storage = MemoryStorage::default();
+    record_matches(&storage).await;
+    let b = storage.find_bot("b").await.unwrap().unwrap();
+    storage
+        .record_practice_match(NewPracticeMatch {
+            players: [Some(b.id), None],
+            builtin: Some("random".to_owned()),
+            outcome: MatchOutcome::Disqualified {
+                loser: 0,
+                reason: Some(DisqualificationReason::TimedOut),
+            },
+            finished_at: 42,
+        })
+        .await
+        .unwrap();
+    let snapshot = transfer::export(&storage, 1000).await.unwrap();
+    assert_eq!(snapshot.practice_matches.len(), 1);
+
+    let directory =
+        std::env::temp_dir().join(format!("match-server-export-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let json = directory.join("export.json");
+    transfer::write_json(&snapshot, &json).unwrap();
+    assert_eq!(transfer::read_json(&json).unwrap(), snapshot);
+    transfer::write_csv(&snapshot, &directory).unwrap();
+    assert_eq!(transfer::read_csv(&directory).unwrap(), snapshot);
+
+    let imported = temporary_database("import");
+    assert_eq!(
+        transfer::import(&imported, snapshot).await.unwrap(),
+        transfer::ImportCounts {
+            bots: 3,
+            matches: 5,
+            practice_matches: 1,
+        }
+    );
+    assert_eq!(
+        transfer::export(&imported, 1000)
+            .await
+            .unwrap()
+            .matches
+            .len(),
+        5
+    );
+
+    // Practice matches follow their bot, whichever id it was given.
+    let b = imported.find_bot("b").await.unwrap().unwrap();
+    let practice_matches = imported.practice_matches_of(b.id).await.unwrap();
+    assert_eq!(practice_matches.len(), 1);
+    assert_eq!(practice_matches[0].builtin.as_deref(), Some("random"));
+
+    // Importing twice would duplicate bots.
+    let snapshot = transfer::export(&storage, 1000).await.unwrap();
+    assert!(matches!(
+        transfer::import(&imported, snapshot).await,
+        Err(transfer::TransferError::NameInUse(_))
+    ));
+}
+
+#[tokio::test]
+async fn bots_without_an_elo_are_exported() {
+    let storage = temporary_database("export-without-elo");
+    record_matches(&storage).await;
+
+    // Databases created before migrations existed may have bots without an elo.
+    rusqlite::Connection::open(temporary_path("export-without-elo"))
+        .unwrap()
+        .execute("UPDATE bots SET elo = NULL WHERE name = 'a'", [])
+        .unwrap();
+    assert_eq!(storage.bots().await.unwrap().len(), 2);
+
+    let snapshot = transfer::export(&storage, 1200).await.unwrap();
+    let a = snapshot.bots.iter().find(|bot| bot.name == "a").unwrap();
+    assert_eq!(a.elo, 1200);
+    assert_eq!(snapshot.bots.len(), 3);
+
+    let imported = MemoryStorage::default();
+    let counts = transfer::import(&imported, snapshot).await.unwrap();
+    assert_eq!((counts.bots, counts.matches), (3, 5));
+}
+
+#[tokio::test]
+async fn faulty_snapshots_are_not_imported() {
+    let storage = MemoryStorage::default();
+    record_matches(&storage).await;
+    let snapshot = transfer::export(&storage, 1000).await.unwrap();
+    let imported = MemoryStorage::default();
+
+    for outcome in [
+        MatchOutcome::Won {
+            winner: 7,
+            delta: 2,
+        },
+        MatchOutcome::Disqualified {
+            loser: 5,
+            reason: None,
+        },
+    ] {
+        let mut faulty = transfer::export(&storage, 1000).await.unwrap();
+        faulty.matches[1].outcome = outcome;
+        assert!(matches!(
+            transfer::import(&imported, faulty).await,
+            Err(transfer::TransferError::InvalidOutcome(_))
+        ));
+    }
+
+    let mut faulty = transfer::export(&storage, 1000).await.unwrap();
+    faulty.practice_matches.push(PracticeMatchRecord {
+        id: 1,
+        players: [Some(u16::MAX), None],
+        builtin: Some("random".to_owned()),
+        outcome: MatchOutcome::Tie,
+        finished_at: 42,
+    });
+    assert!(matches!(
+        transfer::import(&imported, faulty).await,
+        Err(transfer::TransferError::UnknownPracticeBot { .. })
+    ));
+
+    let mut faulty = snapshot;
+    faulty.bots[2].name = faulty.bots[0].name.clone();
+    assert!(matches!(
+        transfer::import(&imported, faulty).await,
+        Err(transfer::TransferError::DuplicateName(_))
+    ));
+
+    // Nothing was left half imported.
+    assert!(imported.bots().await.unwrap().is_empty());
+    assert!(imported.matches().await.unwrap().is_empty());
+    assert!(imported.practice_matches().await.unwrap().is_empty());
+}
+
+async fn import_atomically(storage: &impl Storage) {
+    assert!(storage.insert_bot("taken", "hash", 1000).await.unwrap());
+    let bot = |id, name: &str| BotRecord {
+        id,
+        name: name.to_owned(),
+        elo: 1000,
+        password_hash: "hash".to_owned(),
+    };
+    let matches = vec![NewMatch {
+        players: [7, 9],
+        outcome: MatchOutcome::Tie,
+        elos: [1000, 1000],
+        finished_at: 42,
+    }];
+
+    // The bot registered in the meantime rolls back the ones inserted before it.
+    let result = storage
+        .import(
+            vec![bot(7, "new"), bot(9, "taken")],
+            matches.clone(),
+            Vec::new(),
+        )
+        .await;
+    assert!(matches!(result, Err(StorageError::NameInUse(name)) if name == "taken"));
+    assert_eq!(storage.bots().await.unwrap().len(), 1);
+    assert!(storage.matches().await.unwrap().is_empty());
+
+    storage
+        .import(vec![bot(7, "new"), bot(9, "other")], matches, Vec::new())
+        .await
+        .unwrap();
+    let new = storage.find_bot("new").await.unwrap().unwrap();
+    let other = storage.find_bot("other").await.unwrap().unwrap();
+    assert_eq!(
+        storage.matches().await.unwrap()[0].players,
+        [new.id, other.id]
+    );
+}
+
+#[tokio::test]
+async fn memory_import_atomically() {
+    import_atomically(&MemoryStorage::default()).await;
+}
+
+#[tokio::test]
+async fn database_import_atomically() {
+    import_atomically(&temporary_database("import-atomically")).await;
+}
@@ -0,0 +1,339 @@
+use std::{collections::HashSet, fs::File, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    BotRecord, MatchOutcome, MatchRecord, NewMatch, NewPracticeMatch, PracticeMatchRecord, Storage,
+    StorageError,
+};
+
+/// Everything a ladder is made of: its bots (and their ratings), its match history and the
+/// practice matches played on the side. Exporting a snapshot and importing it in a fresh server
+/// yields the same ladder, although bots and matches may be given different ids.
+#[derive(Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub bots: Vec<BotRecord>,
+    pub matches: Vec<MatchRecord>,
+    /// Missing from snapshots exported before practice matches were.
+    #[serde(default)]
+    pub practice_matches: Vec<PracticeMatchRecord>,
+}
+
+/// Name of the bots' file when a snapshot is stored as CSV.
+const BOTS_CSV: &str = "bots.csv";
+
+/// Name of the matches' file when a snapshot is stored as CSV.
+const MATCHES_CSV: &str = "matches.csv";
+
+/// Name of the practice matches' file when a snapshot is stored as CSV.
+const PRACTICE_MATCHES_CSV: &str = "practice_matches.csv";
+
+/// Reads every bot, match and practice match from the storage. Bots without an elo (see
+/// [`Storage::export_bots`]) are exported with `starting_elo`.
+pub async fn export(storage: &dyn Storage, starting_elo: u16) -> Result<Snapshot, TransferError> {
+    Ok(Snapshot {
+        bots: storage.export_bots(starting_elo).await?,
+        matches: storage.matches().await?,
+        practice_matches: storage.practice_matches().await?,
+    })
+}
+
+/// How many of each were imported, see [`import`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImportCounts {
+    pub bots: usize,
+    pub matches: usize,
+    pub practice_matches: usize,
+}
+
+/// Adds every bot, match and practice match of the snapshot to the storage, returning how many
+/// of each were imported. Nothing is imported if one of the snapshot's bots has a name that is
+/// already taken (or used by another bot of the snapshot), or if one of its matches is invalid.
+pub async fn import(
+    storage: &dyn Storage,
+    snapshot: Snapshot,
+) -> Result<ImportCounts, TransferError> {
+    // Everything is checked beforehand so that a faulty snapshot does not leave the storage half
+    // imported.
+    let mut names = HashSet::new();
+    for bot in snapshot.bots.iter() {
+        if !names.insert(bot.name.as_str()) {
+            return Err(TransferError::DuplicateName(bot.name.clone()));
+        }
+        if storage.find_bot(&bot.name).await?.is_some() {
+            return Err(TransferError::NameInUse(bot.name.clone()));
+        }
+    }
+
+    for record in snapshot.matches.iter() {
+        if !record.outcome.is_valid() {
+            return Err(TransferError::InvalidOutcome(record.id));
+        }
+        for id in record.players {
+            if !snapshot.bots.iter().any(|bot| bot.id == id) {
+                return Err(TransferError::UnknownBot {
+                    match_id: record.id,
+                    bot_id: id,
+                });
+            }
+        }
+    }
+
+    for record in snapshot.practice_matches.iter() {
+        if !record.outcome.is_valid() {
+            return Err(TransferError::InvalidPracticeOutcome(record.id));
+        }
+        for id in record.players.into_iter().flatten() {
+            if !snapshot.bots.iter().any(|bot| bot.id == id) {
+                return Err(TransferError::UnknownPracticeBot {
+                    match_id: record.id,
+                    bot_id: id,
+                });
+            }
+        }
+    }
+
+    let counts = ImportCounts {
+        bots: snapshot.bots.len(),
+        matches: snapshot.matches.len(),
+        practice_matches: snapshot.practice_matches.len(),
+    };
+
+    let matches = snapshot
+        .matches
+        .into_iter()
+        .map(|record| NewMatch {
+            players: record.players,
+            outcome: record.outcome,
+            elos: record.elos,
+            finished_at: record.finished_at,
+        })
+        .collect();
+    let practice_matches = snapshot
+        .practice_matches
+        .into_iter()
+        .map(|record| NewPracticeMatch {
+            players: record.players,
+            builtin: record.builtin,
+            outcome: record.outcome,
+            finished_at: record.finished_at,
+        })
+        .collect();
+
+    // A bot can still be registered between its check and the import, which the storage catches.
+    storage
+        .import(snapshot.bots, matches, practice_matches)
+        .await
+        .map_err(|error| match error {
+            StorageError::NameInUse(name) => TransferError::NameInUse(name),
+            error => error.into(),
+        })?;
+
+    Ok(counts)
+}
+
+/// Writes the snapshot as a single JSON file.
+pub fn write_json(snapshot: &Snapshot, path: &Path) -> Result<(), TransferError> {
+    serde_json::to_writer_pretty(File::create(path)?, snapshot)?;
+    Ok(())
+}
+
+/// Reads a snapshot written by [`write_json`].
+pub fn read_json(path: &Path) -> Result<Snapshot, TransferError> {
+    Ok(serde_json::from_reader(io::BufReader::new(File::open(
+        path,
+    )?))?)
+}
+
+/// Writes the snapshot as a directory containing one CSV file for bots, one for matches and one
+/// for practice matches.
+pub fn write_csv(snapshot: &Snapshot, directory: &Path) -> Result<(), TransferError> {
+    std::fs::create_dir_all(directory)?;
+
+    let mut writer = csv::Writer::from_path(directory.join(BOTS_CSV))?;
+    for bot in snapshot.bots.iter() {
+        writer.serialize(bot)?;
+    }
+    writer.flush()?;
+
+    let mut writer = csv::Writer::from_path(directory.join(MATCHES_CSV))?;
+    for record in snapshot.matches.iter() {
+        writer.serialize(CsvMatch::from(record))?;
+    }
+    writer.flush()?;
+
+    let mut writer = csv::Writer::from_path(directory.join(PRACTICE_MATCHES_CSV))?;
+    for record in snapshot.practice_matches.iter() {
+        writer.serialize(CsvPracticeMatch::from(record))?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Reads a snapshot written by [`write_csv`].
+pub fn read_csv(directory: &Path) -> Result<Snapshot, TransferError> {
+    let bots = csv::Reader::from_path(directory.join(BOTS_CSV))?
+        .deserialize()
+        .collect::<Result<_, _>>()?;
+
+    let matches = csv::Reader::from_path(directory.join(MATCHES_CSV))?
+        .deserialize::<CsvMatch>()
+        .map(|row| {
+            let row = row?;
+            let outcome = MatchOutcome::from_columns(
+                &row.outcome,
+                row.winner,
+                row.score_delta,
+                row.disqualification_reason.as_deref(),
+            )
+            .ok_or(TransferError::InvalidOutcome(row.id))?;
+
+            Ok(MatchRecord {
+                id: row.id,
+                players: [row.first_bot, row.second_bot],
+                outcome,
+                elos: [row.first_elo, row.second_elo],
+                finished_at: row.finished_at,
+            })
+        })
+        .collect::<Result<_, TransferError>>()?;
+
+    // Snapshots exported before practice matches were have no file for them.
+    let practice_matches_path = directory.join(PRACTICE_MATCHES_CSV);
+    let practice_matches = if practice_matches_path.exists() {
+        csv::Reader::from_path(practice_matches_path)?
+            .deserialize::<CsvPracticeMatch>()
+            .map(|row| {
+                let row = row?;
+                let outcome = MatchOutcome::from_columns(
+                    &row.outcome,
+                    row.winner,
+                    row.score_delta,
+                    row.disqualification_reason.as_deref(),
+                )
+                .ok_or(TransferError::InvalidPracticeOutcome(row.id))?;
+
+                Ok(PracticeMatchRecord {
+                    id: row.id,
+                    players: [row.first_bot, row.second_bot],
+                    builtin: row.builtin,
+                    outcome,
+                    finished_at: row.finished_at,
+                })
+            })
+            .collect::<Result<_, TransferError>>()?
+    } else {
+        Vec::new()
+    };
+
+    Ok(Snapshot {
+        bots,
+        matches,
+        practice_matches,
+    })
+}
+
+/// CSV can't represent nested data, so matches are flattened the same way they are stored in the
+/// database.
+#[derive(Serialize, Deserialize)]
+struct CsvMatch {
+    id: u64,
+    first_bot: u16,
+    second_bot: u16,
+    outcome: String,
+    winner: Option<u8>,
+    score_delta: Option<u8>,
+    first_elo: u16,
+    second_elo: u16,
+    finished_at: u64,
+    /// Missing from snapshots exported before reasons were stored.
+    #[serde(default)]
+    disqualification_reason: Option<String>,
+}
+
+impl From<&MatchRecord> for CsvMatch {
+    fn from(record: &MatchRecord) -> Self {
+        let (outcome, winner, score_delta, reason) = record.outcome.to_columns();
+        Self {
+            id: record.id,
+            first_bot: record.players[0],
+            second_bot: record.players[1],
+            outcome: outcome.to_owned(),
+            winner,
+            score_delta,
+            first_elo: record.elos[0],
+            second_elo: record.elos[1],
+            finished_at: record.finished_at,
+            disqualification_reason: reason.map(str::to_owned),
+        }
+    }
+}
+
+/// Practice matches are flattened the same way, with an empty bot id standing for the built-in
+/// bot.
+#[derive(Serialize, Deserialize)]
+struct CsvPracticeMatch {
+    id: u64,
+    first_bot: Option<u16>,
+    second_bot: Option<u16>,
+    builtin: Option<String>,
+    outcome: String,
+    winner: Option<u8>,
+    score_delta: Option<u8>,
+    disqualification_reason: Option<String>,
+    finished_at: u64,
+}
+
+impl From<&PracticeMatchRecord> for CsvPracticeMatch {
+    fn from(record: &PracticeMatchRecord) -> Self {
+        let (outcome, winner, score_delta, reason) = record.outcome.to_columns();
+        Self {
+            id: record.id,
+            first_bot: record.players[0],
+            second_bot: record.players[1],
+            builtin: record.builtin.clone(),
+            outcome: outcome.to_owned(),
+            winner,
+            score_delta,
+            disqualification_reason: reason.map(str::to_owned),
+            finished_at: record.finished_at,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TransferError {
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("csv error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("a bot named {0} already exists")]
+    NameInUse(String),
+
+    #[error("the snapshot contains several bots named {0}")]
+    DuplicateName(String),
+
+    #[error("match {match_id} was played by bot {bot_id}, which is not part of the snapshot")]
+    UnknownBot { match_id: u64, bot_id: u16 },
+
+    #[error("match {0} has an invalid outcome")]
+    InvalidOutcome(u64),
+
+    #[error(
+        "practice match {match_id} was played by bot {bot_id}, which is not part of the snapshot"
+    )]
+    UnknownPracticeBot { match_id: u64, bot_id: u16 },
+
+    #[error("practice match {0} has an invalid outcome")]
+    InvalidPracticeOutcome(u64),
+}
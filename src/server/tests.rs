@@ -0,0 +1,272 @@
+#![cfg(test)]
+
+use std::{future::Future, net::SocketAddr, sync::Arc, time::Duration};
+
+use axum::Router;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::watch,
+    time::Instant,
+};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use super::{
+    api,
+    app_state::{AppState, Bot, DisconnectedBot, Queue},
+    presence::{kick, run_heartbeats},
+    storage::{memory::MemoryStorage, MatchOutcome, Storage},
+};
+use crate::{config::Settings, matchmaker::supervise_matches};
+
+type BotStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Serves the API on a random local port, returning the server's state and address.
+async fn serve(settings: Settings) -> (AppState, SocketAddr) {
+    let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::default());
+    let state = AppState::new(storage, settings);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let routes = Router::new().nest("/api/", api::routes(state.clone()));
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            routes.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+    });
+
+    (state, address)
+}
+
+/// Registers the bot and logs it in with the given extra query parameters, returning its web
+/// socket and session token.
+async fn log_in(address: SocketAddr, name: &str, extra: &str) -> (BotStream, String) {
+    let response = reqwest::get(format!(
+        "http://{address}/api/register?name={name}&password=password"
+    ))
+    .await
+    .unwrap();
+    assert!(response.status().is_success());
+
+    let (stream, response) = tokio_tungstenite::connect_async(format!(
+        "ws://{address}/api/login?name={name}&password=password{extra}"
+    ))
+    .await
+    .unwrap();
+    let token = response.headers()["x-session-token"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    (stream, token)
+}
+
+/// Waits for the bot to be queried, returning the position it is sent.
+async fn next_query(stream: &mut BotStream) -> Value {
+    loop {
+        let Message::Text(text) = stream.next().await.unwrap().unwrap() else {
+            continue;
+        };
+
+        let message: Value = serde_json::from_str(&text).unwrap();
+        if message.get("boards").is_some() {
+            return message;
+        }
+    }
+}
+
+/// Picks the first cell the bot is allowed to play in the given position.
+fn first_valid_move(query: &Value) -> u8 {
+    let cells = |board: &Value| -> Vec<u64> {
+        board
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|seeds| seeds.as_u64().unwrap())
+            .collect()
+    };
+    let (own, other) = (cells(&query["boards"][0]), cells(&query["boards"][1]));
+
+    // A bot whose side is empty plays the other side's cells, numbered from 6.
+    match own.iter().position(|&seeds| seeds != 0) {
+        Some(cell) => cell as u8,
+        None => 6 + other.iter().position(|&seeds| seeds != 0).unwrap() as u8,
+    }
+}
+
+/// Waits for the condition to hold, giving up after a few seconds.
+async fn wait_until<F: Future<Output = bool>>(mut condition: impl FnMut() -> F) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !condition().await {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("the condition never held");
+}
+
+#[tokio::test]
+async fn unresponsive_bots_are_evicted() {
+    let mut settings = Settings::default();
+    settings.presence.heartbeat_interval = Duration::from_millis(100);
+    settings.presence.pong_timeout = Duration::from_millis(200);
+    let (state, address) = serve(settings).await;
+
+    // Pings are only answered whilst the bot reads its web socket.
+    let (mut responsive, _) = log_in(address, "responsive", "").await;
+    tokio::spawn(async move { while let Some(Ok(_)) = responsive.next().await {} });
+    let (_unresponsive, _) = log_in(address, "unresponsive", "").await;
+
+    tokio::spawn(run_heartbeats(state.clone()));
+
+    wait_until(|| async { !state.disconnected_bots.lock().await.is_empty() }).await;
+    // Give the responsive bot a few more heartbeats to be wrongly evicted.
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let disconnected: Vec<_> = state
+        .disconnected_bots
+        .lock()
+        .await
+        .values()
+        .map(|disconnected| disconnected.bot.name.to_string())
+        .collect();
+    assert_eq!(disconnected, ["unresponsive"]);
+    let pending: Vec<_> = state
+        .pending_bots
+        .lock()
+        .await
+        .iter()
+        .map(|bot| bot.name.to_string())
+        .collect();
+    assert_eq!(pending, ["responsive"]);
+}
+
+#[tokio::test]
+async fn bots_can_resume_a_silently_dropped_connection_mid_match() {
+    let mut settings = Settings::default();
+    settings.matchmaking.interval = Duration::from_millis(50);
+    settings.matches.move_timeout = Duration::from_millis(300);
+    let (state, address) = serve(settings).await;
+
+    let (mut dropped, token) = log_in(address, "resumer", "&practice=builtin:greedy").await;
+    tokio::spawn(supervise_matches(state.clone()));
+
+    // The bot stops reading its web socket, so neither the query nor the pings sent alongside
+    // it are answered, as if the connection had silently dropped.
+    next_query(&mut dropped).await;
+    tokio::time::sleep(settings.matches.move_timeout * 2).await;
+
+    let (mut resumed, _) = tokio_tungstenite::connect_async(format!(
+        "ws://{address}/api/resume?name=resumer&token={token}"
+    ))
+    .await
+    .unwrap();
+    tokio::spawn(async move {
+        loop {
+            let query = next_query(&mut resumed).await;
+            let answer = json!({ "value": first_valid_move(&query) }).to_string();
+            resumed.send(Message::text(answer)).await.unwrap();
+        }
+    });
+
+    let id = state.storage.find_bot("resumer").await.unwrap().unwrap().id;
+    wait_until(|| async {
+        !state
+            .storage
+            .practice_matches_of(id)
+            .await
+            .unwrap()
+            .is_empty()
+    })
+    .await;
+
+    let matches = state.storage.practice_matches_of(id).await.unwrap();
+    assert!(!matches!(
+        matches[0].outcome,
+        MatchOutcome::Disqualified { .. }
+    ));
+    drop(dropped);
+}
+
+#[tokio::test]
+async fn kicked_bots_are_forgotten_wherever_they_were() {
+    let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::default());
+    let state = AppState::new(storage, Settings::default());
+    let bot = |id, name: &str| Bot {
+        name: name.into(),
+        id,
+        elo: 1000,
+        socket: None,
+        secret: Arc::new([]),
+        reconnections: Arc::new(watch::channel(0).0),
+        queue: Queue::Ladder,
+        builtin: None,
+    };
+    let (pending, connected, disconnected) =
+        (bot(1, "pending"), bot(2, "connected"), bot(3, "gone"));
+
+    state.pending_bots.lock().await.push(pending.clone());
+    state.connected_bots.lock().await.insert(connected.clone());
+    state.disconnected_bots.lock().await.insert(
+        disconnected.id,
+        DisconnectedBot {
+            bot: disconnected.clone(),
+            was_connected: true,
+            deadline: Instant::now() + Duration::from_secs(60),
+        },
+    );
+    for bot in [&pending, &connected, &disconnected] {
+        state.mark_seen(bot.id);
+    }
+
+    for bot in [&pending, &connected, &disconnected] {
+        kick(&state, bot, "kicked").await;
+    }
+
+    assert!(state.pending_bots.lock().await.is_empty());
+    assert!(state.connected_bots.lock().await.is_empty());
+    assert!(state.disconnected_bots.lock().await.is_empty());
+    assert!(state.presence_lock().is_empty());
+}
+
+#[tokio::test]
+async fn rated_series_need_the_opponent_consent() {
+    let (_, address) = serve(Settings::default()).await;
+    for name in ["challenger", "opponent"] {
+        let response = reqwest::get(format!(
+            "http://{address}/api/register?name={name}&password=password"
+        ))
+        .await
+        .unwrap();
+        assert!(response.status().is_success());
+    }
+
+    let challenge = |extra: &str| {
+        reqwest::get(format!(
+            "http://{address}/api/challenge?name=challenger&password=password&opponent=opponent\
+             &rated=true{extra}"
+        ))
+    };
+    assert_eq!(
+        challenge("").await.unwrap().status(),
+        reqwest::StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        challenge("&opponent_password=guess")
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::UNAUTHORIZED
+    );
+    // With the opponent's consent, the series only fails because neither bot is logged in.
+    assert_eq!(
+        challenge("&opponent_password=password")
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::CONFLICT
+    );
+}